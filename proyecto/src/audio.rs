@@ -1,142 +1,418 @@
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
-use std::fs::File;
-use std::io::BufReader;
-use std::sync::Arc;
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use crate::level::{self, LoadedLevel};
+use crate::map::Map;
+use crate::player::Player;
+use crate::world::WorldEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+    CoinPickup,
+    LevelComplete,
+    MenuMove,
+    MenuSelect,
+    CoinPing,
+    PlayerHurt,
+    ItemPickup,
+    DoorOpen,
+    DoorLocked,
+}
+
+// Candidate files per effect, first one that loads wins. Only the coin sound
+// ships with the game, the others play if present
+const SOUND_FILES: [(SoundEffect, &[&str]); 9] = [
+    (SoundEffect::CoinPickup, &["assets/sounds/coinSound.mp3"]),
+    (SoundEffect::LevelComplete, &["assets/sounds/levelComplete.wav"]),
+    (SoundEffect::MenuMove, &["assets/sounds/menuMove.wav"]),
+    (SoundEffect::MenuSelect, &["assets/sounds/menuSelect.wav"]),
+    (SoundEffect::CoinPing, &["assets/sounds/coinPing.wav", "assets/sounds/coinSound.mp3"]),
+    (SoundEffect::PlayerHurt, &["assets/sounds/hurt.wav"]),
+    (SoundEffect::ItemPickup, &["assets/sounds/pickup.wav", "assets/sounds/coinSound.mp3"]),
+    (SoundEffect::DoorOpen, &["assets/sounds/door.wav"]),
+    (SoundEffect::DoorLocked, &["assets/sounds/locked.wav"]),
+];
+
+// Beyond this many cells a positional sound is silent
+const HEARING_DISTANCE: f32 = 12.0;
+// Gain left when a wall is between the listener and the sound
+const OCCLUSION_GAIN: f32 = 0.35;
+const MIN_AUDIBLE_GAIN: f32 = 0.01;
+
+pub const MUSIC_DIR: &str = "assets/music";
+const MUSIC_EXTENSIONS: [&str; 4] = ["mp3", "ogg", "flac", "wav"];
+// Music plays on two channels so one track can fade out under the next
+pub const MUSIC_CHANNELS: usize = 2;
+// One press of a volume key, in game or on the settings screen
+pub const VOLUME_STEP: f32 = 0.1;
+const CROSSFADE_SECONDS: f32 = 1.5;
+
+// The part of audio that actually makes noise. The game talks to an
+// `AudioManager`, which keeps volumes and decides what to play, and the
+// manager drives one of these: rodio in the game, `NullBackend` when there is
+// no sound device, `RecordingBackend` in tests.
+pub trait AudioBackend {
+    fn load_sound(&mut self, effect: SoundEffect, path: &str) -> Result<(), Box<dyn Error>>;
+    fn play_sound(&mut self, effect: SoundEffect, volume: f32);
+    // `pan` goes from -1 (left only) to 1 (right only)
+    fn play_sound_at(&mut self, effect: SoundEffect, volume: f32, pan: f32);
+    // Starts `path` on `channel` (0..MUSIC_CHANNELS), replacing whatever was
+    // playing there
+    fn play_music(&mut self, channel: usize, path: &str, looping: bool, volume: f32) -> Result<(), Box<dyn Error>>;
+    fn set_music_volume(&mut self, channel: usize, volume: f32);
+    fn stop_music(&mut self, channel: usize);
+    // True once a track that doesn't loop has played to the end
+    fn music_finished(&self, channel: usize) -> bool;
+    // Pausing and resuming apply to every channel
+    fn pause_music(&mut self);
+    fn resume_music(&mut self);
+    fn is_music_playing(&self) -> bool;
+}
+
+// Every music file in `dir` the decoder understands, sorted by name
+pub fn list_music(dir: &str) -> Vec<String> {
+    let mut tracks: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| MUSIC_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+            })
+            .collect(),
+        Err(e) => {
+            println!("Failed to read music directory {}: {}", dir, e);
+            Vec::new()
+        }
+    };
+
+    tracks.sort();
+    tracks.iter().map(|path| path.display().to_string()).collect()
+}
+
+// What plays outside of levels: the tracks in `MUSIC_DIR` that none of
+// `levels` claims for itself, or all of them if every track belongs to a level
+pub fn menu_playlist(levels: &[LoadedLevel]) -> Vec<String> {
+    let tracks = list_music(MUSIC_DIR);
+    let level_tracks = level::music_paths(levels);
+    let free: Vec<String> = tracks
+        .iter()
+        .filter(|track| !level_tracks.iter().any(|used| same_track(used, track)))
+        .cloned()
+        .collect();
+
+    if free.is_empty() { tracks } else { free }
+}
+
+// Level files write paths relative to the game directory, possibly with `./`
+fn same_track(a: &str, b: &str) -> bool {
+    a.trim_start_matches("./") == b.trim_start_matches("./")
+}
+
+struct MusicTrack {
+    channel: usize,
+    // 0..=1 position in the crossfade, multiplied into the music volume
+    fade: f32,
+}
+
+// A sound source in the world that repeats every `interval` seconds
+pub struct Emitter {
+    pub x: f32,
+    pub y: f32,
+    pub effect: SoundEffect,
+    pub volume: f32,
+    pub interval: f32,
+    timer: f32,
+}
+
+// How loud a sound at `emitter` is for `player` and where it sits in the
+// stereo field, as (gain, pan). Gain falls off with the square of the
+// distance up to `HEARING_DISTANCE` and drops further behind walls; pan is
+// -1 for sounds to the player's left and 1 to the right.
+pub fn spatialize(player: &Player, map: &Map, emitter: (f32, f32)) -> (f32, f32) {
+    let dx = emitter.0 - player.x;
+    let dy = emitter.1 - player.y;
+    let distance = (dx * dx + dy * dy).sqrt();
+
+    let falloff = (1.0 - distance / HEARING_DISTANCE).clamp(0.0, 1.0);
+    let mut gain = falloff * falloff;
+    if gain > 0.0 && !map.line_of_sight((player.x, player.y), emitter) {
+        gain *= OCCLUSION_GAIN;
+    }
+
+    // Right of the view direction is angle + PI/2, as for strafing
+    let pan = if distance > 0.0 {
+        (dy.atan2(dx) - player.angle).sin()
+    } else {
+        0.0
+    };
+
+    (gain, pan)
+}
 
 pub struct AudioManager {
-    _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
-    music_sink: Option<Sink>,
+    backend: Box<dyn AudioBackend>,
+    emitters: Vec<Emitter>,
+    playlist: Vec<String>,
+    playlist_index: usize,
+    // The track playing or fading in, and the one fading out under it
+    current_track: Option<MusicTrack>,
+    outgoing_track: Option<MusicTrack>,
+    // 0..=1, kept even while no music is loaded so new tracks start at it
+    music_volume: f32,
+    sfx_volume: f32,
 }
 
 impl AudioManager {
-    pub fn new() -> Self {
-        // Try to initialize audio output
-        match OutputStream::try_default() {
-            Ok((_stream, stream_handle)) => {
-                println!("Audio system initialized successfully!");
-                Self {
-                    _stream,
-                    stream_handle,
-                    music_sink: None,
+    pub fn new(backend: Box<dyn AudioBackend>) -> Self {
+        Self {
+            backend,
+            emitters: Vec::new(),
+            playlist: Vec::new(),
+            playlist_index: 0,
+            current_track: None,
+            outgoing_track: None,
+            music_volume: 0.3,
+            sfx_volume: 0.8,
+        }
+    }
+
+    // For when no output device is available: everything is accepted and
+    // nothing is heard
+    pub fn silent() -> Self {
+        Self::new(Box::new(NullBackend))
+    }
+
+    pub fn load_sounds(&mut self) {
+        for (effect, paths) in SOUND_FILES {
+            for path in paths {
+                match self.backend.load_sound(effect, path) {
+                    Ok(()) => {
+                        println!("Loaded sound effect from: {}", path);
+                        break;
+                    }
+                    Err(e) => println!("Failed to load sound effect from {}: {}", path, e),
                 }
             }
-            Err(e) => {
-                println!("Failed to initialize audio system: {}", e);
-                // Create a dummy stream that won't work but won't crash
-                let (_stream, stream_handle) = OutputStream::try_default().unwrap_or_else(|_| {
-                    // This is a fallback that should never be reached, but prevents compilation errors
-                    panic!("Cannot initialize audio at all")
-                });
-                Self {
-                    _stream,
-                    stream_handle,
-                    music_sink: None,
-                }
+        }
+
+        println!("Audio system ready");
+    }
+
+    pub fn play_sound(&mut self, effect: SoundEffect) {
+        self.backend.play_sound(effect, self.sfx_volume);
+    }
+
+    // Plays a one-shot effect located at (x, y), as heard by `player`
+    pub fn play_sound_at(&mut self, effect: SoundEffect, x: f32, y: f32, player: &Player, map: &Map) {
+        self.play_positioned(effect, 1.0, (x, y), player, map);
+    }
+
+    // The sound for something that happened in the world, as heard by
+    // `player`. A collected coin also stops its ping.
+    pub fn play_event(&mut self, event: &WorldEvent, player: &Player, map: &Map) {
+        match *event {
+            WorldEvent::CoinCollected(x, y) => {
+                self.remove_emitter(x, y);
+                self.play_sound(SoundEffect::CoinPickup);
             }
+            WorldEvent::HealthCollected(_) | WorldEvent::KeyCollected => self.play_sound(SoundEffect::ItemPickup),
+            WorldEvent::PlayerHurt(_) => self.play_sound(SoundEffect::PlayerHurt),
+            WorldEvent::DoorOpened(x, y) => self.play_sound_at(SoundEffect::DoorOpen, x, y, player, map),
+            WorldEvent::DoorLocked => self.play_sound(SoundEffect::DoorLocked),
+            // Played by the screens these lead to
+            WorldEvent::LevelCompleted | WorldEvent::PlayerDied => {}
         }
     }
 
-    pub fn load_sounds(&mut self, _thread: &raylib::prelude::RaylibThread) {
-        println!("Audio system ready");
+    fn play_positioned(&mut self, effect: SoundEffect, volume: f32, at: (f32, f32), player: &Player, map: &Map) {
+        let (gain, pan) = spatialize(player, map, at);
+        let volume = volume * gain * self.sfx_volume;
+        if volume >= MIN_AUDIBLE_GAIN {
+            self.backend.play_sound_at(effect, volume, pan);
+        }
     }
 
-    pub fn start_background_music(&mut self) {
-        self.stop_music(); // Stop any existing music first
-        
-        // Try to load and play the background music
-        let music_paths = [
-            "assets/music/Taylor.wav",
-            "./assets/music/Taylor.wav",
-            "Taylor.wav",
-        ];
+    // `phase` (0..1) offsets the first play so emitters added together don't
+    // all fire on the same frame
+    pub fn add_emitter(&mut self, effect: SoundEffect, x: f32, y: f32, volume: f32, interval: f32, phase: f32) {
+        self.emitters.push(Emitter {
+            x,
+            y,
+            effect,
+            volume,
+            interval,
+            timer: interval * phase.clamp(0.0, 1.0),
+        });
+    }
 
-        for path in &music_paths {
-            match self.load_and_play_music(path) {
-                Ok(_) => {
-                    println!("Started background music from: {}", path);
+    pub fn remove_emitter(&mut self, x: f32, y: f32) {
+        self.emitters.retain(|emitter| emitter.x != x || emitter.y != y);
+    }
+
+    pub fn clear_emitters(&mut self) {
+        self.emitters.clear();
+    }
+
+    pub fn emitters(&self) -> &[Emitter] {
+        &self.emitters
+    }
+
+    // Advances the emitter timers and plays the ones that are due
+    pub fn update_emitters(&mut self, dt: f32, player: &Player, map: &Map) {
+        let mut due = Vec::new();
+        for emitter in &mut self.emitters {
+            emitter.timer -= dt;
+            if emitter.timer <= 0.0 {
+                emitter.timer += emitter.interval;
+                due.push((emitter.effect, emitter.volume, (emitter.x, emitter.y)));
+            }
+        }
+
+        for (effect, volume, at) in due {
+            self.play_positioned(effect, volume, at, player, map);
+        }
+    }
+
+    // Crossfades to `tracks`, played in order and then from the start again.
+    // Asking for the playlist that is already playing changes nothing, so
+    // restarting a level doesn't restart its music; an empty playlist fades
+    // the music out.
+    pub fn play_playlist(&mut self, tracks: &[String]) {
+        if self.playlist == tracks && self.current_track.is_some() {
+            return;
+        }
+
+        self.playlist = tracks.to_vec();
+        self.playlist_index = 0;
+        self.start_track();
+    }
+
+    // Starts the current playlist entry (or the next one that loads) on the
+    // free channel, fading out whatever was playing
+    fn start_track(&mut self) {
+        if let Some(old) = self.outgoing_track.take() {
+            self.backend.stop_music(old.channel);
+        }
+        let channel = match &self.current_track {
+            Some(track) => (track.channel + 1) % MUSIC_CHANNELS,
+            None => 0,
+        };
+        self.outgoing_track = self.current_track.take();
+
+        // A single track loops on its own, longer playlists move on when a
+        // track ends
+        let looping = self.playlist.len() == 1;
+        for attempt in 0..self.playlist.len() {
+            let index = (self.playlist_index + attempt) % self.playlist.len();
+            let path = &self.playlist[index];
+            match self.backend.play_music(channel, path, looping, 0.0) {
+                Ok(()) => {
+                    println!("Playing music from: {}", path);
+                    self.playlist_index = index;
+                    self.current_track = Some(MusicTrack { channel, fade: 0.0 });
                     return;
                 }
-                Err(e) => {
-                    println!("Failed to load music from {}: {}", path, e);
-                    continue;
-                }
+                Err(e) => println!("Failed to load music from {}: {}", path, e),
             }
         }
-        
-        println!("Could not load background music from any path");
-    }
-
-    fn load_and_play_music(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        // Open the audio file
-        let file = File::open(path)?;
-        let buf_reader = BufReader::new(file);
-        
-        // Create a decoder for the audio file
-        let source = Decoder::new(buf_reader)?;
-        
-        // Create a new sink for this music
-        let sink = Sink::try_new(&self.stream_handle)?;
-        
-        // Make the music repeat indefinitely
-        let repeating_source = source.repeat_infinite();
-        
-        // Set a reasonable volume
-        sink.set_volume(0.3);
-        
-        // Append the source to the sink and start playing
-        sink.append(repeating_source);
-        
-        // Store the sink so we can control it later
-        self.music_sink = Some(sink);
-        
-        Ok(())
+
+        if !self.playlist.is_empty() {
+            println!("Could not play any track of the playlist");
+        }
     }
 
-    pub fn update(&mut self) {
-        // Check if music is still playing, restart if needed
-        if let Some(ref sink) = self.music_sink {
-            if sink.empty() {
-                println!("Music finished, restarting...");
-                self.start_background_music();
+    // Advances the crossfade and moves on to the next track when one ends
+    pub fn update(&mut self, dt: f32) {
+        let step = dt / CROSSFADE_SECONDS;
+        let mut fading = false;
+
+        if let Some(track) = &mut self.current_track
+            && track.fade < 1.0
+        {
+            track.fade = (track.fade + step).min(1.0);
+            fading = true;
+        }
+        if let Some(track) = &mut self.outgoing_track {
+            track.fade -= step;
+            fading = true;
+            if track.fade <= 0.0 {
+                self.backend.stop_music(track.channel);
+                self.outgoing_track = None;
             }
         }
+        if fading {
+            self.apply_music_volume();
+        }
+
+        if let Some(track) = &self.current_track
+            && self.backend.music_finished(track.channel)
+        {
+            self.playlist_index = (self.playlist_index + 1) % self.playlist.len();
+            self.start_track();
+        }
     }
 
-    pub fn set_music_volume(&mut self, volume: f32) {
-        if let Some(ref sink) = self.music_sink {
-            sink.set_volume(volume.clamp(0.0, 1.0));
-            println!("Setting music volume to: {:.1}%", volume * 100.0);
+    fn apply_music_volume(&mut self) {
+        for track in self.current_track.iter().chain(self.outgoing_track.iter()) {
+            self.backend.set_music_volume(track.channel, track.fade * self.music_volume);
         }
     }
 
+    pub fn music_volume(&self) -> f32 {
+        self.music_volume
+    }
+
+    pub fn set_music_volume(&mut self, volume: f32) {
+        self.music_volume = volume.clamp(0.0, 1.0);
+        self.apply_music_volume();
+        println!("Setting music volume to: {:.0}%", self.music_volume * 100.0);
+    }
+
+    // Adds `delta` to the music volume and returns the new value
+    pub fn step_music_volume(&mut self, delta: f32) -> f32 {
+        self.set_music_volume(step_volume(self.music_volume, delta));
+        self.music_volume
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.sfx_volume
+    }
+
+    pub fn set_sfx_volume(&mut self, volume: f32) {
+        self.sfx_volume = volume.clamp(0.0, 1.0);
+        println!("Setting effects volume to: {:.0}%", self.sfx_volume * 100.0);
+    }
+
+    // Adds `delta` to the effects volume and returns the new value
+    pub fn step_sfx_volume(&mut self, delta: f32) -> f32 {
+        self.set_sfx_volume(step_volume(self.sfx_volume, delta));
+        self.sfx_volume
+    }
+
     pub fn stop_music(&mut self) {
-        if let Some(sink) = self.music_sink.take() {
-            sink.stop();
+        self.playlist.clear();
+        for track in self.current_track.take().into_iter().chain(self.outgoing_track.take()) {
+            self.backend.stop_music(track.channel);
             println!("Stopping background music");
         }
     }
 
     pub fn pause_music(&mut self) {
-        if let Some(ref sink) = self.music_sink {
-            sink.pause();
-            println!("Pausing background music");
-        }
+        self.backend.pause_music();
+        println!("Pausing background music");
     }
 
     pub fn resume_music(&mut self) {
-        if let Some(ref sink) = self.music_sink {
-            sink.play();
-            println!("Resuming background music");
-        }
+        self.backend.resume_music();
+        println!("Resuming background music");
     }
 
     pub fn is_music_playing(&self) -> bool {
-        if let Some(ref sink) = self.music_sink {
-            !sink.is_paused() && !sink.empty()
-        } else {
-            false
-        }
+        self.backend.is_music_playing()
     }
 }
 
@@ -146,3 +422,358 @@ impl Drop for AudioManager {
         println!("Audio system shutting down");
     }
 }
+
+// `volume` plus `delta`, kept in 0..=1 and snapped to whole percents so
+// repeated steps land exactly on 0 and 1
+pub fn step_volume(volume: f32, delta: f32) -> f32 {
+    ((volume + delta).clamp(0.0, 1.0) * 100.0).round() / 100.0
+}
+
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn load_sound(&mut self, _effect: SoundEffect, _path: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn play_sound(&mut self, _effect: SoundEffect, _volume: f32) {}
+
+    fn play_sound_at(&mut self, _effect: SoundEffect, _volume: f32, _pan: f32) {}
+
+    fn play_music(&mut self, _channel: usize, _path: &str, _looping: bool, _volume: f32) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn set_music_volume(&mut self, _channel: usize, _volume: f32) {}
+
+    fn stop_music(&mut self, _channel: usize) {}
+
+    fn music_finished(&self, _channel: usize) -> bool {
+        false
+    }
+
+    fn pause_music(&mut self) {}
+
+    fn resume_music(&mut self) {}
+
+    fn is_music_playing(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioCall {
+    PlaySound(SoundEffect, f32),
+    // effect, volume, pan
+    PlaySoundAt(SoundEffect, f32, f32),
+    // channel, path, looping
+    PlayMusic(usize, String, bool),
+    SetMusicVolume(usize, f32),
+    StopMusic(usize),
+    PauseMusic,
+    ResumeMusic,
+}
+
+#[derive(Clone, Copy, Default)]
+struct RecordedChannel {
+    playing: bool,
+    finished: bool,
+}
+
+// Remembers every call instead of playing anything. The call log is shared,
+// so keep a clone of the backend to inspect it after handing the original to
+// an `AudioManager`:
+//
+//   let backend = RecordingBackend::new();
+//   let mut audio = AudioManager::new(Box::new(backend.clone()));
+//   audio.play_sound(SoundEffect::CoinPickup);
+//   assert_eq!(backend.sounds_played(), vec![SoundEffect::CoinPickup]);
+#[derive(Clone, Default)]
+pub struct RecordingBackend {
+    calls: Rc<RefCell<Vec<AudioCall>>>,
+    channels: Rc<RefCell<[RecordedChannel; MUSIC_CHANNELS]>>,
+    paused: Rc<RefCell<bool>>,
+}
+
+impl RecordingBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn calls(&self) -> Vec<AudioCall> {
+        self.calls.borrow().clone()
+    }
+
+    pub fn sounds_played(&self) -> Vec<SoundEffect> {
+        self.calls
+            .borrow()
+            .iter()
+            .filter_map(|call| match call {
+                AudioCall::PlaySound(effect, _) | AudioCall::PlaySoundAt(effect, _, _) => Some(*effect),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.calls.borrow_mut().clear();
+    }
+
+    // Pretends the track on `channel` has played to the end
+    pub fn finish_music(&self, channel: usize) {
+        self.channels.borrow_mut()[channel].finished = true;
+    }
+
+    fn record(&self, call: AudioCall) {
+        self.calls.borrow_mut().push(call);
+    }
+}
+
+impl AudioBackend for RecordingBackend {
+    // Every sound "loads", so tests don't depend on the asset files
+    fn load_sound(&mut self, _effect: SoundEffect, _path: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn play_sound(&mut self, effect: SoundEffect, volume: f32) {
+        self.record(AudioCall::PlaySound(effect, volume));
+    }
+
+    fn play_sound_at(&mut self, effect: SoundEffect, volume: f32, pan: f32) {
+        self.record(AudioCall::PlaySoundAt(effect, volume, pan));
+    }
+
+    fn play_music(&mut self, channel: usize, path: &str, looping: bool, _volume: f32) -> Result<(), Box<dyn Error>> {
+        self.record(AudioCall::PlayMusic(channel, path.to_string(), looping));
+        self.channels.borrow_mut()[channel] = RecordedChannel {
+            playing: true,
+            finished: false,
+        };
+        Ok(())
+    }
+
+    fn set_music_volume(&mut self, channel: usize, volume: f32) {
+        self.record(AudioCall::SetMusicVolume(channel, volume));
+    }
+
+    fn stop_music(&mut self, channel: usize) {
+        self.record(AudioCall::StopMusic(channel));
+        self.channels.borrow_mut()[channel] = RecordedChannel::default();
+    }
+
+    fn music_finished(&self, channel: usize) -> bool {
+        self.channels.borrow()[channel].finished
+    }
+
+    fn pause_music(&mut self) {
+        self.record(AudioCall::PauseMusic);
+        *self.paused.borrow_mut() = true;
+    }
+
+    fn resume_music(&mut self) {
+        self.record(AudioCall::ResumeMusic);
+        *self.paused.borrow_mut() = false;
+    }
+
+    fn is_music_playing(&self) -> bool {
+        !*self.paused.borrow()
+            && self
+                .channels
+                .borrow()
+                .iter()
+                .any(|channel| channel.playing && !channel.finished)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+    use crate::entity;
+    use crate::input::PlayerCommand;
+    use crate::world::World;
+
+    fn level_one() -> Map {
+        Map::load(Path::new("assets/levels/level1.level")).unwrap()
+    }
+
+    fn recording() -> (AudioManager, RecordingBackend) {
+        let backend = RecordingBackend::new();
+        (AudioManager::new(Box::new(backend.clone())), backend)
+    }
+
+    // Same calls in the same order, with volumes compared loosely
+    fn assert_calls(actual: Vec<AudioCall>, expected: &[AudioCall]) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        let matches = actual.len() == expected.len()
+            && actual.iter().zip(expected).all(|pair| match pair {
+                (AudioCall::SetMusicVolume(a, x), AudioCall::SetMusicVolume(b, y)) => a == b && close(*x, *y),
+                (a, b) => a == b,
+            });
+        assert!(matches, "calls {:?}\nexpected {:?}", actual, expected);
+    }
+
+    #[test]
+    fn collecting_a_coin_plays_the_pickup_and_stops_its_ping() {
+        let (mut audio, backend) = recording();
+        let mut world = World::from_map(0, level_one(), entity::SPARKLE_LIFETIME);
+        let (x, y) = world.map.get_coin_positions()[0];
+        audio.add_emitter(SoundEffect::CoinPing, x, y, 1.0, 2.5, 0.5);
+
+        world.player.x = x;
+        world.player.y = y;
+        for event in world.update(&PlayerCommand::default(), 1.0 / 60.0) {
+            audio.play_event(&event, &world.player, &world.map);
+        }
+
+        assert_eq!(backend.sounds_played(), vec![SoundEffect::CoinPickup]);
+        assert!(audio.emitters().is_empty());
+    }
+
+    #[test]
+    fn world_events_play_their_sounds() {
+        let (mut audio, backend) = recording();
+        let map = level_one();
+        let player = Player::new(1.5, 1.5, 0.0);
+        let events = [
+            WorldEvent::CoinCollected(12.5, 3.5),
+            WorldEvent::HealthCollected(100),
+            WorldEvent::KeyCollected,
+            WorldEvent::PlayerHurt(60),
+            WorldEvent::DoorOpened(2.5, 1.5),
+            WorldEvent::DoorLocked,
+            WorldEvent::LevelCompleted,
+            WorldEvent::PlayerDied,
+        ];
+        for event in &events {
+            audio.play_event(event, &player, &map);
+        }
+
+        assert_eq!(
+            backend.sounds_played(),
+            vec![
+                SoundEffect::CoinPickup,
+                SoundEffect::ItemPickup,
+                SoundEffect::ItemPickup,
+                SoundEffect::PlayerHurt,
+                SoundEffect::DoorOpen,
+                SoundEffect::DoorLocked,
+            ]
+        );
+        // The door is straight ahead of a player facing east, so it isn't panned
+        assert!(matches!(backend.calls()[4], AudioCall::PlaySoundAt(SoundEffect::DoorOpen, _, pan) if pan.abs() < 1e-5));
+    }
+
+    #[test]
+    fn sounds_pan_to_their_side_and_fade_behind_walls() {
+        let map = level_one();
+        // Facing east along the open row 1, so +y is to the right
+        let player = Player::new(1.5, 1.5, 0.0);
+
+        let (ahead, pan) = spatialize(&player, &map, (3.5, 1.5));
+        assert!(pan.abs() < 1e-5, "pan {}", pan);
+        let (_, pan) = spatialize(&player, &map, (1.5, 2.5));
+        assert!((pan - 1.0).abs() < 1e-5, "pan {}", pan);
+
+        let (farther, _) = spatialize(&player, &map, (5.5, 1.5));
+        assert!(farther < ahead);
+        // As far away as a point down the open row, but behind the type 2
+        // wall at (2, 2)
+        let (occluded, _) = spatialize(&player, &map, (3.5, 3.5));
+        let (open, _) = spatialize(&player, &map, (1.5 + 8.0f32.sqrt(), 1.5));
+        assert!(occluded < open * 0.9, "{} vs {}", occluded, open);
+    }
+
+    #[test]
+    fn effects_play_at_the_effects_volume() {
+        let (mut audio, backend) = recording();
+        audio.set_sfx_volume(0.5);
+        audio.play_sound(SoundEffect::MenuMove);
+
+        assert_eq!(backend.calls(), vec![AudioCall::PlaySound(SoundEffect::MenuMove, 0.5)]);
+    }
+
+    #[test]
+    fn a_new_playlist_crossfades_on_the_other_channel() {
+        let (mut audio, backend) = recording();
+        let volume = audio.music_volume();
+        audio.play_playlist(&["a.ogg".to_string()]);
+        audio.update(CROSSFADE_SECONDS / 3.0);
+        assert_calls(
+            backend.calls(),
+            &[AudioCall::PlayMusic(0, "a.ogg".to_string(), true), AudioCall::SetMusicVolume(0, volume / 3.0)],
+        );
+
+        backend.clear();
+        audio.play_playlist(&["b.ogg".to_string()]);
+        audio.update(CROSSFADE_SECONDS / 6.0);
+        audio.update(CROSSFADE_SECONDS / 6.0);
+        assert_calls(
+            backend.calls(),
+            &[
+                AudioCall::PlayMusic(1, "b.ogg".to_string(), true),
+                // The new track comes in while the old one goes out
+                AudioCall::SetMusicVolume(1, volume / 6.0),
+                AudioCall::SetMusicVolume(0, volume / 6.0),
+                // The old one is stopped once silent
+                AudioCall::StopMusic(0),
+                AudioCall::SetMusicVolume(1, volume / 3.0),
+            ],
+        );
+
+        // Asking again for what is playing changes nothing
+        backend.clear();
+        audio.play_playlist(&["b.ogg".to_string()]);
+        assert_calls(backend.calls(), &[]);
+    }
+
+    #[test]
+    fn a_finished_track_moves_to_the_next_one() {
+        let (mut audio, backend) = recording();
+        audio.play_playlist(&["a.ogg".to_string(), "b.ogg".to_string()]);
+        audio.update(CROSSFADE_SECONDS);
+        backend.clear();
+
+        backend.finish_music(0);
+        audio.update(0.0);
+        assert_eq!(backend.calls(), vec![AudioCall::PlayMusic(1, "b.ogg".to_string(), false)]);
+    }
+
+    #[test]
+    fn volume_steps_land_on_whole_percents_and_stop_at_the_ends() {
+        let mut volume = 0.0;
+        for _ in 0..10 {
+            volume = step_volume(volume, VOLUME_STEP);
+        }
+        assert_eq!(volume, 1.0);
+        assert_eq!(step_volume(volume, VOLUME_STEP), 1.0);
+        assert_eq!(step_volume(0.3, -VOLUME_STEP), 0.2);
+        assert_eq!(step_volume(0.05, -VOLUME_STEP), 0.0);
+    }
+
+    #[test]
+    fn the_null_backend_takes_every_call() {
+        let mut audio = AudioManager::silent();
+        let map = level_one();
+        let player = Player::new(1.5, 1.5, 0.0);
+
+        audio.load_sounds();
+        audio.play_sound(SoundEffect::MenuMove);
+        audio.play_sound_at(SoundEffect::CoinPing, 3.5, 1.5, &player, &map);
+        audio.play_event(&WorldEvent::DoorOpened(2.5, 1.5), &player, &map);
+        audio.add_emitter(SoundEffect::CoinPing, 12.5, 3.5, 1.0, 0.5, 0.0);
+        audio.play_playlist(&["missing.ogg".to_string(), "also-missing.ogg".to_string()]);
+        for _ in 0..200 {
+            audio.update(0.05);
+            audio.update_emitters(0.05, &player, &map);
+        }
+        audio.step_music_volume(0.1);
+        audio.step_sfx_volume(-0.1);
+        audio.pause_music();
+        audio.resume_music();
+        audio.play_playlist(&[]);
+        audio.stop_music();
+
+        assert!(!audio.is_music_playing());
+    }
+}
@@ -0,0 +1,87 @@
+// The plain text format shared by the level, settings, save and sprite sheet
+// files: `key = value` lines, `[name]` lines that start a section, `#` to the
+// end of the line is a comment and blank lines are skipped. What the keys and
+// sections mean, and how bad lines are reported, is up to each file.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry<'a> {
+    // `[name]`, with the name trimmed
+    Section(&'a str),
+    // Key and value trimmed, and the 1-based column the value starts at
+    Pair { key: &'a str, value: &'a str, value_column: usize },
+    // Neither, like the rows of a level grid
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line<'a> {
+    // 1-based line number and column of the first character
+    pub number: usize,
+    pub column: usize,
+    // The line without its comment and surrounding spaces
+    pub text: &'a str,
+    pub entry: Entry<'a>,
+}
+
+// The non-blank lines of `source`, comments removed
+pub fn lines(source: &str) -> impl Iterator<Item = Line<'_>> {
+    source.lines().enumerate().filter_map(|(index, raw_line)| {
+        let line = match raw_line.find('#') {
+            Some(comment) => &raw_line[..comment],
+            None => raw_line,
+        };
+        let text = line.trim();
+        if text.is_empty() {
+            return None;
+        }
+
+        let entry = if let Some(name) = text.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            Entry::Section(name.trim())
+        } else if let Some((key, value)) = line.split_once('=') {
+            let value_column = key.len() + 2 + (value.len() - value.trim_start().len());
+            Entry::Pair { key: key.trim(), value: value.trim(), value_column }
+        } else {
+            Entry::Text
+        };
+
+        Some(Line {
+            number: index + 1,
+            column: line.len() - line.trim_start().len() + 1,
+            text,
+            entry,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_skip_comments_and_blanks() {
+        let source = "# header\n\n  fov = 60   # degrees\n[ level1.level ]\n  1D01\n";
+        let lines: Vec<Line> = lines(source).collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                Line {
+                    number: 3,
+                    column: 3,
+                    text: "fov = 60",
+                    entry: Entry::Pair { key: "fov", value: "60", value_column: 9 },
+                },
+                Line { number: 4, column: 1, text: "[ level1.level ]", entry: Entry::Section("level1.level") },
+                Line { number: 5, column: 3, text: "1D01", entry: Entry::Text },
+            ]
+        );
+    }
+
+    #[test]
+    fn values_keep_inner_spaces_and_may_be_empty() {
+        let lines: Vec<Line> = lines("decoration = 4.5 4.5 barrel.png\nmusic =\n").collect();
+
+        assert_eq!(lines[0].entry, Entry::Pair { key: "decoration", value: "4.5 4.5 barrel.png", value_column: 14 });
+        assert_eq!(lines[1].entry, Entry::Pair { key: "music", value: "", value_column: 8 });
+    }
+}
@@ -0,0 +1,161 @@
+use raylib::prelude::*;
+use proyecto::input::{Action, Control, PlayerCommand, CONTROLS};
+use proyecto::settings::Settings;
+
+// Menu navigation, which can't be rebound
+const FIXED_KEYS: [(KeyboardKey, Action); 7] = [
+    (KeyboardKey::KEY_ENTER, Action::Confirm),
+    (KeyboardKey::KEY_TAB, Action::Settings),
+    (KeyboardKey::KEY_UP, Action::MenuUp),
+    (KeyboardKey::KEY_DOWN, Action::MenuDown),
+    (KeyboardKey::KEY_LEFT, Action::MenuLeft),
+    (KeyboardKey::KEY_RIGHT, Action::MenuRight),
+    (KeyboardKey::KEY_L, Action::Leaderboard),
+];
+
+// Names used in the settings file, raylib's `KEY_*` without the prefix
+const KEY_NAMES: [(&str, KeyboardKey); 66] = [
+    ("A", KeyboardKey::KEY_A),
+    ("B", KeyboardKey::KEY_B),
+    ("C", KeyboardKey::KEY_C),
+    ("D", KeyboardKey::KEY_D),
+    ("E", KeyboardKey::KEY_E),
+    ("F", KeyboardKey::KEY_F),
+    ("G", KeyboardKey::KEY_G),
+    ("H", KeyboardKey::KEY_H),
+    ("I", KeyboardKey::KEY_I),
+    ("J", KeyboardKey::KEY_J),
+    ("K", KeyboardKey::KEY_K),
+    ("L", KeyboardKey::KEY_L),
+    ("M", KeyboardKey::KEY_M),
+    ("N", KeyboardKey::KEY_N),
+    ("O", KeyboardKey::KEY_O),
+    ("P", KeyboardKey::KEY_P),
+    ("Q", KeyboardKey::KEY_Q),
+    ("R", KeyboardKey::KEY_R),
+    ("S", KeyboardKey::KEY_S),
+    ("T", KeyboardKey::KEY_T),
+    ("U", KeyboardKey::KEY_U),
+    ("V", KeyboardKey::KEY_V),
+    ("W", KeyboardKey::KEY_W),
+    ("X", KeyboardKey::KEY_X),
+    ("Y", KeyboardKey::KEY_Y),
+    ("Z", KeyboardKey::KEY_Z),
+    ("ZERO", KeyboardKey::KEY_ZERO),
+    ("ONE", KeyboardKey::KEY_ONE),
+    ("TWO", KeyboardKey::KEY_TWO),
+    ("THREE", KeyboardKey::KEY_THREE),
+    ("FOUR", KeyboardKey::KEY_FOUR),
+    ("FIVE", KeyboardKey::KEY_FIVE),
+    ("SIX", KeyboardKey::KEY_SIX),
+    ("SEVEN", KeyboardKey::KEY_SEVEN),
+    ("EIGHT", KeyboardKey::KEY_EIGHT),
+    ("NINE", KeyboardKey::KEY_NINE),
+    ("SPACE", KeyboardKey::KEY_SPACE),
+    ("ESCAPE", KeyboardKey::KEY_ESCAPE),
+    ("ENTER", KeyboardKey::KEY_ENTER),
+    ("TAB", KeyboardKey::KEY_TAB),
+    ("BACKSPACE", KeyboardKey::KEY_BACKSPACE),
+    ("LEFT", KeyboardKey::KEY_LEFT),
+    ("RIGHT", KeyboardKey::KEY_RIGHT),
+    ("UP", KeyboardKey::KEY_UP),
+    ("DOWN", KeyboardKey::KEY_DOWN),
+    ("MINUS", KeyboardKey::KEY_MINUS),
+    ("EQUAL", KeyboardKey::KEY_EQUAL),
+    ("LEFT_BRACKET", KeyboardKey::KEY_LEFT_BRACKET),
+    ("RIGHT_BRACKET", KeyboardKey::KEY_RIGHT_BRACKET),
+    ("SEMICOLON", KeyboardKey::KEY_SEMICOLON),
+    ("APOSTROPHE", KeyboardKey::KEY_APOSTROPHE),
+    ("COMMA", KeyboardKey::KEY_COMMA),
+    ("PERIOD", KeyboardKey::KEY_PERIOD),
+    ("SLASH", KeyboardKey::KEY_SLASH),
+    ("BACKSLASH", KeyboardKey::KEY_BACKSLASH),
+    ("GRAVE", KeyboardKey::KEY_GRAVE),
+    ("LEFT_SHIFT", KeyboardKey::KEY_LEFT_SHIFT),
+    ("RIGHT_SHIFT", KeyboardKey::KEY_RIGHT_SHIFT),
+    ("LEFT_CONTROL", KeyboardKey::KEY_LEFT_CONTROL),
+    ("RIGHT_CONTROL", KeyboardKey::KEY_RIGHT_CONTROL),
+    ("LEFT_ALT", KeyboardKey::KEY_LEFT_ALT),
+    ("RIGHT_ALT", KeyboardKey::KEY_RIGHT_ALT),
+    ("F1", KeyboardKey::KEY_F1),
+    ("F2", KeyboardKey::KEY_F2),
+    ("F3", KeyboardKey::KEY_F3),
+    ("F4", KeyboardKey::KEY_F4),
+];
+
+pub fn key_named(name: &str) -> Option<KeyboardKey> {
+    KEY_NAMES.iter().find(|(key_name, _)| *key_name == name).map(|(_, key)| *key)
+}
+
+pub fn key_name(key: KeyboardKey) -> Option<&'static str> {
+    KEY_NAMES.iter().find(|(_, named)| *named == key).map(|(name, _)| *name)
+}
+
+// The rebindable keys resolved from the settings. Names raylib doesn't know
+// fall back to the control's default key.
+pub struct KeyMap {
+    keys: Vec<(Control, KeyboardKey)>,
+}
+
+impl KeyMap {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let keys = CONTROLS
+            .iter()
+            .map(|control| {
+                let name = settings.key(*control);
+                let key = key_named(name).unwrap_or_else(|| {
+                    println!("Unknown key '{}' for {}, using {}", name, control.config_name(), control.default_key());
+                    key_named(control.default_key()).expect("default keys are in the key table")
+                });
+                (*control, key)
+            })
+            .collect();
+
+        Self { keys }
+    }
+
+    fn key(&self, control: Control) -> KeyboardKey {
+        self.keys
+            .iter()
+            .find(|(bound, _)| *bound == control)
+            .map(|(_, key)| *key)
+            .expect("every control is bound")
+    }
+}
+
+// Turns this frame's keyboard and mouse state into a command: movement,
+// turning, mouse look and the action keys
+pub fn read_command(rl: &RaylibHandle, keys: &KeyMap) -> PlayerCommand {
+    let axis = |positive: Control, negative: Control| {
+        let mut value = 0.0;
+        if rl.is_key_down(keys.key(positive)) {
+            value += 1.0;
+        }
+        if rl.is_key_down(keys.key(negative)) {
+            value -= 1.0;
+        }
+        value
+    };
+
+    let mut cmd = PlayerCommand::new(
+        axis(Control::Forward, Control::Back),
+        axis(Control::StrafeRight, Control::StrafeLeft),
+        axis(Control::TurnRight, Control::TurnLeft),
+        rl.get_mouse_delta().x,
+    );
+
+    for (key, action) in FIXED_KEYS {
+        if rl.is_key_pressed(key) {
+            cmd.actions.push(action);
+        }
+    }
+    for (control, key) in &keys.keys {
+        if let Some(action) = control.action()
+            && rl.is_key_pressed(*key)
+        {
+            cmd.actions.push(action);
+        }
+    }
+
+    cmd
+}
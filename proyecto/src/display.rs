@@ -0,0 +1,112 @@
+use raylib::prelude::*;
+use proyecto::framebuffer::{FrameBuffer, TextureData, rgba};
+use proyecto::raycast::RayCaster;
+use proyecto::level::{self, LoadedLevel};
+use proyecto::entity;
+use proyecto::sprite_sheet::SpriteSheet;
+
+// Raylib side of the software renderer: decodes image files into CPU
+// textures for the raycaster and uploads its framebuffer once per frame,
+// stretched over the whole window whatever size it was rendered at
+pub struct Display {
+    frame_texture: Option<Texture2D>,
+}
+
+impl Display {
+    pub fn new() -> Self {
+        Self { frame_texture: None }
+    }
+
+    pub fn init(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, width: usize, height: usize) {
+        let image = Image::gen_image_color(width as i32, height as i32, Color::BLACK);
+        match rl.load_texture_from_image(thread, &image) {
+            Ok(texture) => self.frame_texture = Some(texture),
+            Err(e) => println!("Failed to create frame texture: {}", e),
+        }
+    }
+
+    pub fn present(&mut self, d: &mut RaylibDrawHandle, framebuffer: &FrameBuffer) {
+        if let Some(texture) = &mut self.frame_texture
+            && let Err(e) = texture.update_texture(framebuffer.as_bytes())
+        {
+            println!("Failed to upload frame: {}", e);
+            return;
+        }
+        self.redraw(d);
+    }
+
+    // Shows the last presented frame again without uploading a new one
+    pub fn redraw(&self, d: &mut RaylibDrawHandle) {
+        if let Some(texture) = &self.frame_texture {
+            let source = Rectangle::new(0.0, 0.0, texture.width as f32, texture.height as f32);
+            let target = Rectangle::new(0.0, 0.0, d.get_screen_width() as f32, d.get_screen_height() as f32);
+            d.draw_texture_pro(texture, source, target, Vector2::zero(), 0.0, Color::WHITE);
+        }
+    }
+}
+
+pub fn load_texture_data(path: &str) -> Result<TextureData, String> {
+    let image = Image::load_image(path).map_err(|e| e.to_string())?;
+    let pixels = image
+        .get_image_data()
+        .iter()
+        .map(|c| rgba(c.r, c.g, c.b, c.a))
+        .collect();
+
+    Ok(TextureData::new(image.width as usize, image.height as usize, pixels))
+}
+
+pub fn load_textures(raycaster: &mut RayCaster, levels: &[LoadedLevel]) {
+    // Load the sprites of the built-in entities and the decorations used by
+    // any level. Missing ones are drawn as coloured circles
+    let sprites = entity::BUILTIN_SPRITES
+        .iter()
+        .map(|path| path.to_string())
+        .chain(level::sprite_paths(levels));
+    for path in sprites {
+        match load_texture_data(&path) {
+            Ok(texture) => {
+                println!("Loaded sprite from: {}", path);
+                let sheet = SpriteSheet::load_for(&path);
+                raycaster.add_sprite_texture(path, texture, sheet);
+            }
+            Err(e) => {
+                println!("Failed to load sprite from {}: {}", path, e);
+            }
+        }
+    }
+
+    // Load the wall textures used by any level, once each
+    for path in level::texture_paths(levels) {
+        match load_texture_data(&path) {
+            Ok(texture) => {
+                println!("Loaded wall texture from: {}", path);
+                raycaster.add_wall_texture(path, texture);
+            }
+            Err(e) => {
+                println!("Failed to load wall texture from {}: {}", path, e);
+            }
+        }
+    }
+
+    // Load floor texture
+    let floor_texture_paths = [
+        "assets/textures/ground.jpg",
+        "assets/textures/Ground2.jpg",
+        "ground.jpg",
+    ];
+
+    for path in &floor_texture_paths {
+        match load_texture_data(path) {
+            Ok(texture) => {
+                println!("Loaded floor texture from: {}", path);
+                raycaster.set_floor_texture(texture);
+                break;
+            }
+            Err(e) => {
+                println!("Failed to load floor texture from {}: {}", path, e);
+                continue;
+            }
+        }
+    }
+}
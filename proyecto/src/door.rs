@@ -0,0 +1,207 @@
+// Grid value of a door cell. Level files mark doors with letters, which the
+// parser turns into this value plus a `Door` with the state.
+pub const DOOR_CELL: u8 = 10;
+
+// Seconds to slide fully open or shut
+const SLIDE_SECONDS: f32 = 0.8;
+// Seconds an open door waits, with nothing in the doorway, before closing
+const STAY_OPEN_SECONDS: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorKind {
+    // Opened with the use key
+    Use,
+    // Opens by itself when the player comes close
+    Auto,
+    // Like `Use`, but the first opening takes one of the player's keys
+    Locked,
+}
+
+impl DoorKind {
+    // Letter in the level grid
+    pub fn from_grid(letter: char) -> Option<Self> {
+        match letter {
+            'D' => Some(DoorKind::Use),
+            'A' => Some(DoorKind::Auto),
+            'L' => Some(DoorKind::Locked),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorMotion {
+    Closed,
+    Opening,
+    Open,
+    Closing,
+}
+
+// A slab across the middle of its cell that slides sideways into the wall
+#[derive(Debug, Clone)]
+pub struct Door {
+    pub x: usize,
+    pub y: usize,
+    pub kind: DoorKind,
+    // The slab runs along y, at x + 0.5, when the door sits between walls
+    // above and below; otherwise it runs along x, at y + 0.5
+    pub vertical: bool,
+    // How far the slab has slid, 0 shut to 1 fully open
+    pub open_amount: f32,
+    pub motion: DoorMotion,
+    timer: f32,
+}
+
+impl Door {
+    pub fn new(x: usize, y: usize, kind: DoorKind, vertical: bool) -> Self {
+        Self {
+            x,
+            y,
+            kind,
+            vertical,
+            open_amount: 0.0,
+            motion: DoorMotion::Closed,
+            timer: 0.0,
+        }
+    }
+
+    // Only a fully open door can be walked or seen through
+    pub fn is_passable(&self) -> bool {
+        self.open_amount >= 1.0
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x as f32 + 0.5, self.y as f32 + 0.5)
+    }
+
+    // Starts opening, or keeps an open door open for longer. Returns whether
+    // the door started moving.
+    pub fn open(&mut self) -> bool {
+        match self.motion {
+            DoorMotion::Closed | DoorMotion::Closing => {
+                self.motion = DoorMotion::Opening;
+                true
+            }
+            DoorMotion::Open => {
+                self.timer = STAY_OPEN_SECONDS;
+                false
+            }
+            DoorMotion::Opening => false,
+        }
+    }
+
+    // Advances the slide by `dt` seconds. An `occupied` doorway keeps the
+    // door from closing on whatever stands in it.
+    pub fn update(&mut self, dt: f32, occupied: bool) {
+        match self.motion {
+            DoorMotion::Closed => {}
+            DoorMotion::Opening => {
+                self.open_amount += dt / SLIDE_SECONDS;
+                if self.open_amount >= 1.0 {
+                    self.open_amount = 1.0;
+                    self.motion = DoorMotion::Open;
+                    self.timer = STAY_OPEN_SECONDS;
+                }
+            }
+            DoorMotion::Open => {
+                if occupied {
+                    self.timer = STAY_OPEN_SECONDS;
+                } else {
+                    self.timer -= dt;
+                    if self.timer <= 0.0 {
+                        self.motion = DoorMotion::Closing;
+                    }
+                }
+            }
+            DoorMotion::Closing => {
+                if occupied {
+                    self.motion = DoorMotion::Opening;
+                } else {
+                    self.open_amount -= dt / SLIDE_SECONDS;
+                    if self.open_amount <= 0.0 {
+                        self.open_amount = 0.0;
+                        self.motion = DoorMotion::Closed;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+    use crate::entity;
+    use crate::input::{Action, PlayerCommand};
+    use crate::level;
+    use crate::map::Map;
+    use crate::world::{World, WorldEvent};
+
+    fn door() -> Door {
+        Door::new(2, 1, DoorKind::Use, true)
+    }
+
+    #[test]
+    fn a_door_slides_open_waits_and_slides_shut() {
+        let mut door = door();
+        assert!(door.open());
+
+        door.update(SLIDE_SECONDS / 2.0, false);
+        assert_eq!((door.motion, door.open_amount), (DoorMotion::Opening, 0.5));
+        assert!(!door.is_passable());
+        door.update(SLIDE_SECONDS / 2.0, false);
+        assert_eq!((door.motion, door.open_amount), (DoorMotion::Open, 1.0));
+        assert!(door.is_passable());
+
+        door.update(STAY_OPEN_SECONDS - 0.1, false);
+        assert_eq!(door.motion, DoorMotion::Open);
+        door.update(0.2, false);
+        assert_eq!(door.motion, DoorMotion::Closing);
+
+        door.update(SLIDE_SECONDS / 2.0, false);
+        assert_eq!((door.motion, door.open_amount), (DoorMotion::Closing, 0.5));
+        door.update(SLIDE_SECONDS / 2.0, false);
+        assert_eq!((door.motion, door.open_amount), (DoorMotion::Closed, 0.0));
+    }
+
+    #[test]
+    fn an_occupied_doorway_keeps_the_door_from_closing() {
+        let mut door = door();
+        door.open();
+        door.update(SLIDE_SECONDS, false);
+
+        door.update(STAY_OPEN_SECONDS * 10.0, true);
+        assert_eq!(door.motion, DoorMotion::Open);
+
+        // Leaving restarts the wait, and stepping back in while it closes
+        // opens it again
+        door.update(STAY_OPEN_SECONDS - 0.1, false);
+        assert_eq!(door.motion, DoorMotion::Open);
+        door.update(0.2, false);
+        door.update(SLIDE_SECONDS / 2.0, false);
+        assert_eq!(door.motion, DoorMotion::Closing);
+        door.update(0.1, true);
+        assert_eq!((door.motion, door.open_amount), (DoorMotion::Opening, 0.5));
+    }
+
+    #[test]
+    fn a_locked_door_stays_shut_until_the_player_has_a_key() {
+        let level = level::parse_level(Path::new("test.level"), "spawn = 1.5 1.5 0.0\n[grid]\n11111\n10L01\n11111\n");
+        let mut world = World::from_map(0, Map::from_level(level.unwrap()), entity::SPARKLE_LIFETIME);
+        let use_key = PlayerCommand { actions: vec![Action::Use], ..PlayerCommand::default() };
+
+        // The player faces the door, a cell to the east
+        let events = world.update(&use_key, 0.1);
+        assert!(events.contains(&WorldEvent::DoorLocked));
+        world.update(&PlayerCommand::default(), SLIDE_SECONDS);
+        assert_eq!(world.map.door_at(2, 1).unwrap().motion, DoorMotion::Closed);
+
+        world.keys = 1;
+        let events = world.update(&use_key, 0.1);
+        assert!(events.contains(&WorldEvent::DoorOpened(2.5, 1.5)));
+        assert_eq!(world.keys, 0);
+        let door = world.map.door_at(2, 1).unwrap();
+        assert_eq!((door.kind, door.motion), (DoorKind::Use, DoorMotion::Opening));
+    }
+}
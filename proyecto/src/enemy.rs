@@ -0,0 +1,311 @@
+use std::f32::consts::PI;
+use crate::map::Map;
+use crate::pathfind::{self, Cell};
+use crate::player::Player;
+
+pub const ENEMY_RADIUS: f32 = 0.3;
+// Cells the enemy can see the player from, with a clear line of sight
+const SIGHT_RANGE: f32 = 8.0;
+// Width of the view cone while patrolling; once chasing the enemy keeps track
+// of the player in every direction
+const VIEW_ANGLE: f32 = 2.0 * PI / 3.0;
+// Closer than this the player is noticed even from behind
+const NOTICE_DISTANCE: f32 = 1.5;
+const PATROL_SPEED: f32 = 1.2;
+const CHASE_SPEED: f32 = 2.2;
+// Seconds without seeing the player before giving up the chase
+const LOSE_SIGHT_SECONDS: f32 = 3.0;
+// Paths are recomputed at most this often while the target moves
+const REPATH_INTERVAL: f32 = 0.4;
+const ATTACK_RANGE: f32 = 0.7;
+const ATTACK_DAMAGE: i32 = 20;
+const ATTACK_COOLDOWN: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnemyState {
+    // Walking the route from the level file
+    Patrol,
+    // Following the player, or where the player was last seen
+    Chase,
+    // Lost the player, walking back to the route
+    Return,
+}
+
+#[derive(Clone)]
+pub struct Enemy {
+    pub x: f32,
+    pub y: f32,
+    // Facing, in radians like `Player::angle`
+    pub angle: f32,
+    pub state: EnemyState,
+    route: Vec<(f32, f32)>,
+    waypoint: usize,
+    // Cells still to walk, and the cell they lead to. `None` until a path
+    // is found, so a failed search is tried again
+    path: Vec<Cell>,
+    path_target: Option<Cell>,
+    repath_timer: f32,
+    last_seen: (f32, f32),
+    lost_timer: f32,
+    attack_timer: f32,
+}
+
+impl Enemy {
+    // `route` is the patrol route from the level file, never empty
+    pub fn new(route: Vec<(f32, f32)>) -> Self {
+        let (x, y) = route[0];
+        let angle = match route.get(1) {
+            Some(next) => (next.1 - y).atan2(next.0 - x),
+            None => 0.0,
+        };
+
+        Self {
+            x,
+            y,
+            angle,
+            state: EnemyState::Patrol,
+            route,
+            waypoint: 0,
+            path: Vec::new(),
+            path_target: None,
+            repath_timer: 0.0,
+            last_seen: (x, y),
+            lost_timer: 0.0,
+            attack_timer: 0.0,
+        }
+    }
+
+    // Advances the enemy by `dt` seconds. Returns the damage dealt to the
+    // player this step, if it attacked.
+    pub fn update(&mut self, dt: f32, player: &Player, map: &Map) -> Option<i32> {
+        let sees_player = self.can_see(player, map);
+        if sees_player {
+            self.last_seen = (player.x, player.y);
+            self.lost_timer = 0.0;
+        }
+
+        match self.state {
+            EnemyState::Patrol | EnemyState::Return if sees_player => self.set_state(EnemyState::Chase),
+            EnemyState::Chase if !sees_player => {
+                self.lost_timer += dt;
+                if self.lost_timer >= LOSE_SIGHT_SECONDS {
+                    self.set_state(EnemyState::Return);
+                }
+            }
+            _ => {}
+        }
+
+        match self.state {
+            EnemyState::Patrol => {
+                if self.walk_to(self.route[self.waypoint], PATROL_SPEED, dt, map) {
+                    self.waypoint = (self.waypoint + 1) % self.route.len();
+                }
+            }
+            EnemyState::Return => {
+                if self.walk_to(self.route[self.waypoint], PATROL_SPEED, dt, map) {
+                    self.set_state(EnemyState::Patrol);
+                }
+            }
+            EnemyState::Chase => {
+                // Stops short of the player instead of walking into them
+                if !(sees_player && self.distance_to(player.x, player.y) < ATTACK_RANGE * 0.8) {
+                    self.walk_to(self.last_seen, CHASE_SPEED, dt, map);
+                }
+                if sees_player {
+                    self.angle = (player.y - self.y).atan2(player.x - self.x);
+                }
+            }
+        }
+
+        self.attack_timer = (self.attack_timer - dt).max(0.0);
+        if sees_player && self.attack_timer <= 0.0 && self.distance_to(player.x, player.y) < ATTACK_RANGE {
+            self.attack_timer = ATTACK_COOLDOWN;
+            return Some(ATTACK_DAMAGE);
+        }
+
+        None
+    }
+
+    // In range, inside the view cone unless already chasing or very close,
+    // and with no wall in between
+    pub fn can_see(&self, player: &Player, map: &Map) -> bool {
+        let distance = self.distance_to(player.x, player.y);
+        if distance > SIGHT_RANGE {
+            return false;
+        }
+
+        if self.state != EnemyState::Chase && distance > NOTICE_DISTANCE {
+            let to_player = (player.y - self.y).atan2(player.x - self.x);
+            let mut offset = (to_player - self.angle) % (2.0 * PI);
+            if offset > PI {
+                offset -= 2.0 * PI;
+            } else if offset < -PI {
+                offset += 2.0 * PI;
+            }
+            if offset.abs() > VIEW_ANGLE / 2.0 {
+                return false;
+            }
+        }
+
+        map.line_of_sight((self.x, self.y), (player.x, player.y))
+    }
+
+    fn set_state(&mut self, state: EnemyState) {
+        self.state = state;
+        self.path.clear();
+        self.path_target = None;
+        self.repath_timer = 0.0;
+        self.lost_timer = 0.0;
+    }
+
+    fn distance_to(&self, x: f32, y: f32) -> f32 {
+        ((x - self.x).powi(2) + (y - self.y).powi(2)).sqrt()
+    }
+
+    // Moves towards `target` along an A* path through cell centres, finishing
+    // on the exact point. Returns whether the target was reached. An
+    // unreachable target leaves the enemy where it is, searching again every
+    // `REPATH_INTERVAL` in case a door opens.
+    fn walk_to(&mut self, target: (f32, f32), speed: f32, dt: f32, map: &Map) -> bool {
+        let target_cell = pathfind::cell_at(target.0, target.1);
+        self.repath_timer -= dt;
+        if self.path_target != Some(target_cell) && self.repath_timer <= 0.0 {
+            let path = pathfind::find_path(map, pathfind::cell_at(self.x, self.y), target_cell);
+            self.path_target = path.is_some().then_some(target_cell);
+            self.path = path.unwrap_or_default();
+            self.repath_timer = REPATH_INTERVAL;
+        }
+
+        // A door shut on the path since it was planned
+        if self.path.iter().any(|cell| !map.is_walkable(cell.0, cell.1)) {
+            self.path.clear();
+            self.path_target = None;
+            return false;
+        }
+
+        let mut budget = speed * dt;
+        loop {
+            let goal = match self.path.first() {
+                Some(cell) if *cell != target_cell => pathfind::cell_center(*cell),
+                Some(_) => target,
+                // Only finish on the target from inside its cell, never
+                // through a wall when no path was found
+                None if pathfind::cell_at(self.x, self.y) == target_cell => target,
+                None => return false,
+            };
+
+            let (dx, dy) = (goal.0 - self.x, goal.1 - self.y);
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance > 0.0 {
+                self.angle = dy.atan2(dx);
+            }
+
+            if distance > budget {
+                self.x += dx / distance * budget;
+                self.y += dy / distance * budget;
+                return false;
+            }
+
+            self.x = goal.0;
+            self.y = goal.1;
+            budget -= distance;
+            if self.path.is_empty() {
+                return true;
+            }
+            self.path.remove(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+    use crate::door::DoorMotion;
+    use crate::level;
+
+    // A corridor with the patrol route, and a sealed room below it
+    const CORRIDOR_LEVEL: &str = "\
+spawn = 1.5 1.5 0.0
+
+[grid]
+1111111
+1000001
+1111111
+1000001
+1111111
+";
+
+    #[test]
+    fn enemies_chase_what_they_see_and_return_to_their_route() {
+        let level = level::parse_level(Path::new("test.level"), CORRIDOR_LEVEL).unwrap();
+        let map = Map::from_level(level);
+        let mut enemy = Enemy::new(vec![(1.5, 1.5), (5.5, 1.5)]);
+        let mut player = Player::new(4.5, 1.5, 0.0);
+
+        // Ahead of the enemy, inside its view cone
+        assert_eq!(enemy.update(0.1, &player, &map), None);
+        assert_eq!(enemy.state, EnemyState::Chase);
+
+        // Out of sight, the enemy keeps going to where the player was
+        player = Player::new(3.5, 3.5, 0.0);
+        let mut seconds = 0.1;
+        let mut reached_last_seen = false;
+        while enemy.state == EnemyState::Chase {
+            reached_last_seen |= enemy.distance_to(4.5, 1.5) < 0.01;
+            assert_eq!(enemy.update(0.1, &player, &map), None);
+            seconds += 0.1;
+            assert!(seconds < 4.0, "still chasing after {} seconds", seconds);
+        }
+        assert!(reached_last_seen);
+        assert_eq!(enemy.state, EnemyState::Return);
+        assert!(seconds >= LOSE_SIGHT_SECONDS);
+
+        while enemy.state == EnemyState::Return {
+            enemy.update(0.1, &player, &map);
+            seconds += 0.1;
+            assert!(seconds < 10.0, "still returning after {} seconds", seconds);
+        }
+        assert_eq!(enemy.state, EnemyState::Patrol);
+    }
+
+    // Two rooms joined by a door at (3, 3); the player waits out of sight in
+    // the corridor above
+    const DOOR_LEVEL: &str = "\
+spawn = 1.5 1.5 0.0
+
+[grid]
+1111111
+1000001
+1111111
+100D001
+1001001
+1111111
+";
+
+    #[test]
+    fn enemy_walks_on_once_a_closed_door_opens() {
+        let level = level::parse_level(Path::new("test.level"), DOOR_LEVEL).unwrap();
+        let mut map = Map::from_level(level);
+        let player = Player::new(1.5, 1.5, 0.0);
+        let mut enemy = Enemy::new(vec![(1.5, 3.5), (5.5, 3.5)]);
+
+        for _ in 0..20 {
+            enemy.update(0.1, &player, &map);
+        }
+        assert!(enemy.x < 3.0, "walked through a closed door");
+        let stuck_at = (enemy.x, enemy.y);
+
+        let door = map.door_at_mut(3, 3).unwrap();
+        door.open_amount = 1.0;
+        door.motion = DoorMotion::Open;
+        // The route walks back once it reaches the far room
+        let mut furthest = enemy.x;
+        for _ in 0..60 {
+            enemy.update(0.1, &player, &map);
+            furthest = furthest.max(enemy.x);
+        }
+        assert_ne!((enemy.x, enemy.y), stuck_at, "stuck after the door opened");
+        assert!(furthest > 5.0, "never got through the door");
+    }
+}
@@ -0,0 +1,219 @@
+use crate::enemy::{Enemy, ENEMY_RADIUS};
+use crate::framebuffer::rgba;
+use crate::level::Placement;
+use crate::sprite_sheet::SpriteSheet;
+
+pub type EntityId = u32;
+
+// Sprite paths of the built-in entity types. An entity whose sprite isn't
+// loaded is drawn as a circle of its `color` instead
+pub const COIN_SPRITE: &str = "assets/sprites/coin.png";
+pub const ENEMY_SPRITE: &str = "assets/sprites/enemy.png";
+pub const HEALTH_PACK_SPRITE: &str = "assets/sprites/health.png";
+pub const KEY_SPRITE: &str = "assets/sprites/key.png";
+pub const SPARKLE_SPRITE: &str = "assets/sprites/sparkle.png";
+pub const BUILTIN_SPRITES: [&str; 5] = [COIN_SPRITE, ENEMY_SPRITE, HEALTH_PACK_SPRITE, KEY_SPRITE, SPARKLE_SPRITE];
+
+pub const HEALTH_PACK_AMOUNT: i32 = 25;
+
+// What the world does with an entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behaviour {
+    // Picked up on touch; collecting them all completes the level
+    Coin,
+    // Picked up on touch when the player is hurt
+    HealthPack,
+    // Picked up on touch and kept for the rest of the attempt
+    Key,
+    // Only drawn
+    Decoration,
+    // Moved by the `Enemy` in `Entity::enemy`
+    Enemy,
+    // Plays for its `lifetime` and is removed
+    Effect,
+}
+
+impl Behaviour {
+    pub fn is_pickup(self) -> bool {
+        matches!(self, Behaviour::Coin | Behaviour::HealthPack | Behaviour::Key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Animation {
+    Still,
+    // Grows and shrinks around the base scale
+    Pulse,
+}
+
+// Seconds a pickup sparkle without an animated sheet stays on screen
+pub const SPARKLE_LIFETIME: f32 = 0.4;
+
+// How long a sparkle drawn with `sheet` lives: one pass through its frames,
+// or `SPARKLE_LIFETIME` for a still image
+pub fn sparkle_lifetime(sheet: &SpriteSheet) -> f32 {
+    if sheet.columns > 1 { sheet.duration() } else { SPARKLE_LIFETIME }
+}
+
+// Anything in the level drawn as a sprite. The renderer only looks at the
+// position, sprite, colour, scale, offset and animation, so new kinds of
+// entity need no renderer changes.
+#[derive(Clone)]
+pub struct Entity {
+    pub id: EntityId,
+    pub x: f32,
+    pub y: f32,
+    // Facing, in radians like `Player::angle`
+    pub angle: f32,
+    pub sprite: String,
+    pub color: u32,
+    // Height relative to half a wall
+    pub scale: f32,
+    // Raises the sprite by this fraction of a wall height, negative to lower it
+    pub vertical_offset: f32,
+    pub animation: Animation,
+    // Seconds into the animation. Entities start at different phases so
+    // identical ones don't move in step
+    pub animation_time: f32,
+    // Touching distance, in cells
+    pub radius: f32,
+    pub behaviour: Behaviour,
+    // Cleared when a pickup is collected, so it is no longer drawn or touched
+    pub active: bool,
+    // Patrol and chase state, for `Behaviour::Enemy`
+    pub enemy: Option<Enemy>,
+    // Seconds an effect lives
+    pub lifetime: Option<f32>,
+}
+
+impl Entity {
+    pub fn new(id: EntityId, x: f32, y: f32, sprite: &str, behaviour: Behaviour) -> Self {
+        Self {
+            id,
+            x,
+            y,
+            angle: 0.0,
+            sprite: sprite.to_string(),
+            color: rgba(130, 130, 130, 255),
+            scale: 1.0,
+            vertical_offset: 0.0,
+            animation: Animation::Still,
+            animation_time: phase(id),
+            radius: 0.3,
+            behaviour,
+            active: true,
+            enemy: None,
+            lifetime: None,
+        }
+    }
+
+    // A sparkle where something was picked up, starting from its first frame
+    pub fn sparkle(id: EntityId, x: f32, y: f32, lifetime: f32) -> Self {
+        Self {
+            color: rgba(255, 240, 150, 255),
+            scale: 0.5,
+            animation_time: 0.0,
+            radius: 0.0,
+            lifetime: Some(lifetime),
+            ..Self::new(id, x, y, SPARKLE_SPRITE, Behaviour::Effect)
+        }
+    }
+
+    // Multiplier on `scale` for the current frame
+    pub fn animation_scale(&self) -> f32 {
+        match self.animation {
+            Animation::Still => 1.0,
+            Animation::Pulse => 1.0 + (self.animation_time * 3.0).sin() * 0.1,
+        }
+    }
+
+    pub fn update_animation(&mut self, dt: f32) {
+        self.animation_time += dt;
+        if self.lifetime.is_some_and(|lifetime| self.animation_time >= lifetime) {
+            self.active = false;
+        }
+    }
+
+    pub fn from_placement(id: EntityId, placement: &Placement) -> Self {
+        match placement {
+            Placement::Coin(x, y) => Self {
+                color: rgba(255, 203, 0, 255),
+                animation: Animation::Pulse,
+                ..Self::new(id, *x, *y, COIN_SPRITE, Behaviour::Coin)
+            },
+            Placement::Enemy(route) => {
+                let enemy = Enemy::new(route.clone());
+                Self {
+                    angle: enemy.angle,
+                    color: rgba(200, 30, 30, 255),
+                    scale: 1.6,
+                    radius: ENEMY_RADIUS,
+                    enemy: Some(enemy),
+                    ..Self::new(id, route[0].0, route[0].1, ENEMY_SPRITE, Behaviour::Enemy)
+                }
+            }
+            Placement::HealthPack(x, y) => Self {
+                color: rgba(0, 200, 80, 255),
+                scale: 0.6,
+                vertical_offset: -0.35,
+                ..Self::new(id, *x, *y, HEALTH_PACK_SPRITE, Behaviour::HealthPack)
+            },
+            Placement::Key(x, y) => Self {
+                color: rgba(80, 180, 255, 255),
+                scale: 0.5,
+                vertical_offset: -0.2,
+                animation: Animation::Pulse,
+                ..Self::new(id, *x, *y, KEY_SPRITE, Behaviour::Key)
+            },
+            // Standing on the floor
+            Placement::Decoration(sprite, x, y) => Self {
+                vertical_offset: -0.25,
+                radius: 0.0,
+                ..Self::new(id, *x, *y, sprite, Behaviour::Decoration)
+            },
+        }
+    }
+}
+
+// Spreads the starting phases of entities over a few seconds, the same for
+// every run of a level
+fn phase(id: EntityId) -> f32 {
+    (id as f32 * 0.618).fract() * 4.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprite_sheet::LoopMode;
+
+    #[test]
+    fn a_sparkle_lives_for_one_pass_through_its_sheet() {
+        let sheet = SpriteSheet {
+            columns: 6,
+            frame_duration: 0.05,
+            loop_mode: LoopMode::Once,
+            ..SpriteSheet::default()
+        };
+        let mut sparkle = Entity::sparkle(0, 1.5, 1.5, sparkle_lifetime(&sheet));
+
+        sparkle.update_animation(0.25);
+        assert!(sparkle.active);
+        sparkle.update_animation(0.06);
+        assert!(!sparkle.active);
+        assert_eq!(sparkle_lifetime(&SpriteSheet::default()), SPARKLE_LIFETIME);
+    }
+
+    #[test]
+    fn entities_start_at_different_phases() {
+        let coin = Placement::Coin(1.5, 1.5);
+        let mut first = Entity::from_placement(0, &coin);
+        let mut second = Entity::from_placement(1, &coin);
+        assert_ne!(first.animation_time, second.animation_time);
+
+        // The offset stays as both play on
+        let offset = second.animation_time - first.animation_time;
+        first.update_animation(0.5);
+        second.update_animation(0.5);
+        assert!((second.animation_time - first.animation_time - offset).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+// CPU-side RGBA image the raycaster draws into. Colors are packed so that the
+// bytes of each u32 in memory are r, g, b, a, which is the layout raylib
+// expects when the whole buffer is uploaded as a texture.
+
+pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> u32 {
+    u32::from_ne_bytes([r, g, b, a])
+}
+
+pub fn channels(color: u32) -> [u8; 4] {
+    color.to_ne_bytes()
+}
+
+// Multiplies the color channels by `brightness`, keeping alpha
+pub fn shade(color: u32, brightness: f32) -> u32 {
+    let [r, g, b, a] = channels(color);
+    rgba(
+        (r as f32 * brightness) as u8,
+        (g as f32 * brightness) as u8,
+        (b as f32 * brightness) as u8,
+        a,
+    )
+}
+
+// `src` drawn over `dst` by the alpha of `src`. The result keeps the alpha of
+// `dst`, which in the framebuffer is always opaque
+pub fn blend(src: u32, dst: u32) -> u32 {
+    let [sr, sg, sb, sa] = channels(src);
+    let [dr, dg, db, da] = channels(dst);
+    match sa {
+        0 => dst,
+        255 => rgba(sr, sg, sb, da),
+        _ => {
+            let alpha = sa as u32;
+            let mix = |s: u8, d: u8| ((s as u32 * alpha + d as u32 * (255 - alpha) + 127) / 255) as u8;
+            rgba(mix(sr, dr), mix(sg, dg), mix(sb, db), da)
+        }
+    }
+}
+
+pub struct FrameBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![rgba(0, 0, 0, 255); width * height],
+        }
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        self.pixels.fill(color);
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> u32 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    // Draws `color` over the pixel by its alpha, see `blend`
+    pub fn blend_pixel(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            let index = y * self.width + x;
+            self.pixels[index] = blend(color, self.pixels[index]);
+        }
+    }
+
+    // Fills rows `y_start..y_end` of column `x`, clipped to the buffer
+    pub fn draw_column(&mut self, x: usize, y_start: i32, y_end: i32, color: u32) {
+        if x >= self.width {
+            return;
+        }
+        let y_start = y_start.clamp(0, self.height as i32) as usize;
+        let y_end = y_end.clamp(0, self.height as i32) as usize;
+
+        for y in y_start..y_end {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: u32 has no padding and any byte pattern is a valid u8, the
+        // slice covers exactly the memory owned by `pixels`
+        unsafe { std::slice::from_raw_parts(self.pixels.as_ptr() as *const u8, self.pixels.len() * 4) }
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let file = File::create(path)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+// Decoded image the software renderer samples from
+pub struct TextureData {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl TextureData {
+    pub fn new(width: usize, height: usize, pixels: Vec<u32>) -> Self {
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> u32 {
+        self.pixels[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_keep_their_channels() {
+        let color = rgba(10, 20, 30, 40);
+        assert_eq!(channels(color), [10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn shade_scales_color_but_not_alpha() {
+        assert_eq!(channels(shade(rgba(200, 100, 50, 128), 0.5)), [100, 50, 25, 128]);
+        assert_eq!(channels(shade(rgba(200, 100, 50, 255), 0.0)), [0, 0, 0, 255]);
+        assert_eq!(shade(rgba(200, 100, 50, 255), 1.0), rgba(200, 100, 50, 255));
+    }
+
+    #[test]
+    fn blend_mixes_by_the_source_alpha_and_keeps_the_destination_alpha() {
+        let dst = rgba(0, 255, 100, 255);
+        assert_eq!(blend(rgba(255, 0, 100, 0), dst), dst);
+        assert_eq!(blend(rgba(255, 0, 100, 255), dst), rgba(255, 0, 100, 255));
+        assert_eq!(channels(blend(rgba(255, 0, 100, 128), dst)), [128, 127, 100, 255]);
+        assert_eq!(channels(blend(rgba(255, 0, 0, 255), rgba(0, 0, 0, 0))), [255, 0, 0, 0]);
+    }
+
+    #[test]
+    fn blend_pixel_draws_over_what_is_there() {
+        let mut frame = FrameBuffer::new(2, 1);
+        frame.clear(rgba(0, 0, 0, 255));
+        frame.blend_pixel(1, 0, rgba(200, 100, 0, 128));
+        frame.blend_pixel(5, 0, rgba(200, 100, 0, 255));
+
+        assert_eq!(frame.get_pixel(0, 0), rgba(0, 0, 0, 255));
+        assert_eq!(channels(frame.get_pixel(1, 0)), [100, 50, 0, 255]);
+    }
+
+    #[test]
+    fn draw_column_fills_only_its_rows() {
+        let mut framebuffer = FrameBuffer::new(3, 4);
+        let red = rgba(255, 0, 0, 255);
+        framebuffer.draw_column(1, 1, 3, red);
+
+        for y in 0..4 {
+            let expected = if (1..3).contains(&y) { red } else { rgba(0, 0, 0, 255) };
+            assert_eq!(framebuffer.get_pixel(1, y), expected, "row {}", y);
+            assert_eq!(framebuffer.get_pixel(0, y), rgba(0, 0, 0, 255));
+            assert_eq!(framebuffer.get_pixel(2, y), rgba(0, 0, 0, 255));
+        }
+    }
+
+    #[test]
+    fn draw_column_clips_to_the_buffer() {
+        let mut framebuffer = FrameBuffer::new(2, 3);
+        let white = rgba(255, 255, 255, 255);
+        framebuffer.draw_column(0, -5, 10, white);
+        // Off the right edge, ignored
+        framebuffer.draw_column(2, 0, 3, white);
+
+        assert!((0..3).all(|y| framebuffer.get_pixel(0, y) == white));
+        assert!((0..3).all(|y| framebuffer.get_pixel(1, y) == rgba(0, 0, 0, 255)));
+    }
+
+    #[test]
+    fn bytes_are_rgba_in_row_order() {
+        let mut framebuffer = FrameBuffer::new(2, 2);
+        framebuffer.clear(rgba(0, 0, 0, 0));
+        framebuffer.set_pixel(1, 0, rgba(1, 2, 3, 4));
+        framebuffer.set_pixel(0, 1, rgba(5, 6, 7, 8));
+
+        assert_eq!(framebuffer.get_pixel(1, 0), rgba(1, 2, 3, 4));
+        assert_eq!(
+            framebuffer.as_bytes(),
+            &[0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn texture_sampling_clamps_to_the_edges() {
+        let pixels = (0..6).map(|i| rgba(i, 0, 0, 255)).collect();
+        let texture = TextureData::new(3, 2, pixels);
+
+        assert_eq!(texture.get_pixel(0, 0), rgba(0, 0, 0, 255));
+        assert_eq!(texture.get_pixel(2, 0), rgba(2, 0, 0, 255));
+        assert_eq!(texture.get_pixel(1, 1), rgba(4, 0, 0, 255));
+        assert_eq!(texture.get_pixel(7, 9), rgba(5, 0, 0, 255));
+    }
+}
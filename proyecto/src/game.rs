@@ -1,182 +1,621 @@
 use raylib::prelude::*;
-use crate::player::Player;
-use crate::map::Map;
-use crate::sprite::SpriteManager;
-use crate::raycast::RayCaster;
+use proyecto::audio::{self, AudioManager, SoundEffect, VOLUME_STEP};
+use proyecto::entity;
+use proyecto::input::{Action, Control, PlayerCommand};
+use proyecto::settings_menu::{SettingsChange, SettingsMenu};
+use proyecto::pause::{PauseItem, PauseMenu, PAUSE_ITEMS};
+use proyecto::progress::{self, Progress};
+use proyecto::leaderboard::{self, Leaderboard, Run};
+use proyecto::level;
+use proyecto::player;
+use proyecto::raycast::RayCaster;
+use proyecto::settings::{self, Settings};
+use proyecto::sprite_sheet::SpriteSheet;
+use proyecto::state::{GameState, StateMachine, Transition};
+use proyecto::world::{World, WorldEvent};
 use crate::minimap::MiniMap;
-use crate::audio::AudioManager;
-use crate::menu::Menu;
+use crate::rodio_backend;
+use crate::menu::{self, Menu, MenuEvent};
+use crate::display::{self, Display};
+use crate::controls::{self, KeyMap};
 
-const SCREEN_WIDTH: i32 = 1024;
+// Uncollected coins ping every few seconds so they can be found by ear
+const COIN_PING_INTERVAL: f32 = 2.5;
+const COIN_PING_VOLUME: f32 = 0.6;
+// Pause menu layout, shared by drawing and mouse picking. The list is
+// centred on the window
+const PAUSE_ITEM_SPACING: i32 = 45;
+const PAUSE_ITEM_SIZE: i32 = 28;
+const PAUSE_ITEM_WIDTH: i32 = 300;
+// Settings rows shown at once at most; the list scrolls to keep the selection
+// visible, and shows fewer rows in small windows
+const SETTINGS_VISIBLE_ROWS: usize = 12;
+const SETTINGS_ROW_HEIGHT: i32 = 30;
+const SETTINGS_TOP: i32 = 280;
+// The hurt flash starts fading this long before the invulnerability ends
+const HURT_FLASH_SECONDS: f32 = 0.5;
+// Seconds a message like "Locked" stays on screen
+const HINT_SECONDS: f32 = 2.0;
 
 pub struct Game {
-    pub player: Player,
-    pub map: Map,
-    pub sprites: SpriteManager,
+    pub world: World,
     pub raycaster: RayCaster,
+    pub display: Display,
     pub minimap: MiniMap,
     pub audio: AudioManager,
     pub menu: Menu,
-    pub current_level: usize,
-    pub in_menu: bool,
-    pub game_won: bool,
-    pub music_started: bool,
+    pub pause_menu: PauseMenu,
+    pub settings_menu: SettingsMenu,
+    pub settings: Settings,
+    pub keys: KeyMap,
+    pub progress: Progress,
+    pub leaderboard: Leaderboard,
+    // The run that just finished and its place on the leaderboard, for the
+    // level complete screen
+    last_run: Option<(Run, Option<usize>)>,
+    // Save file names of the levels, in menu order
+    level_ids: Vec<String>,
+    pub states: StateMachine,
+    // Set when the pause screen paused the music, so leaving it resumes it
+    music_paused: bool,
+    // Played in the menu and in levels that don't name their own music
+    pub menu_music: Vec<String>,
+    // Short message under the crosshair and the seconds it has left
+    hint: Option<(&'static str, f32)>,
 }
 
 impl Game {
-    pub fn new(_rl: &mut RaylibHandle, _thread: &RaylibThread) -> Self {
-        let level = 0;
-        let map = Map::new(level);
-        let sprites = SpriteManager::new(&map);
-        let mut audio = AudioManager::new();
-
-        Self {
-            player: Player::new(1.5, 1.5, 0.0),
-            map,
-            sprites,
-            raycaster: RayCaster::new(),
+    pub fn new(_rl: &mut RaylibHandle, _thread: &RaylibThread, settings: Settings) -> Self {
+        let mut audio = rodio_backend::open_audio();
+        audio.set_music_volume(settings.music_volume);
+        audio.set_sfx_volume(settings.sfx_volume);
+
+        let sparkle_lifetime = entity::sparkle_lifetime(&SpriteSheet::load_for(entity::SPARKLE_SPRITE));
+        let levels = level::load_levels(level::LEVELS_DIR);
+
+        let mut game = Self {
+            world: World::new(0, sparkle_lifetime),
+            raycaster: RayCaster::new(1, 1),
+            display: Display::new(),
             minimap: MiniMap::new(),
             audio,
-            menu: Menu::new(),
-            current_level: level,
-            in_menu: true,
-            game_won: false,
-            music_started: false,
-        }
+            menu: Menu::new(level::level_names(&levels)),
+            pause_menu: PauseMenu::new(),
+            settings_menu: SettingsMenu::new(),
+            keys: KeyMap::from_settings(&settings),
+            settings,
+            progress: Progress::load(progress::PROGRESS_PATH.as_ref()),
+            leaderboard: Leaderboard::load(leaderboard::LEADERBOARD_PATH.as_ref()),
+            last_run: None,
+            level_ids: level::level_ids(&levels),
+            states: StateMachine::new(GameState::Title),
+            music_paused: false,
+            menu_music: audio::menu_playlist(&levels),
+            hint: None,
+        };
+        game.refresh_menu_progress();
+        game.enter_state(GameState::Title, None);
+        game
     }
 
     pub fn load_textures(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) {
-        self.raycaster.load_textures(rl, thread);
-        self.audio.load_sounds(thread);
+        display::load_textures(&mut self.raycaster, &level::load_levels(level::LEVELS_DIR));
+        self.resize_renderer(rl, thread);
+        self.audio.load_sounds();
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.states.should_quit()
     }
 
-    pub fn update(&mut self, rl: &mut RaylibHandle) {
-        // Start music when first entering the menu (delayed start)
-        if !self.music_started {
-            self.audio.start_background_music();
-            self.music_started = true;
+    pub fn update(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) {
+        let cmd = controls::read_command(rl, &self.keys);
+        let dt = rl.get_frame_time();
+
+        // A window resized by hand keeps its size for the next run
+        if rl.is_window_resized() && !rl.is_window_fullscreen() {
+            self.settings.width = rl.get_screen_width() as u32;
+            self.settings.height = rl.get_screen_height() as u32;
+            self.resize_renderer(rl, thread);
+            self.save_settings();
         }
 
-        if self.in_menu {
-            if let Some(level) = self.menu.update(rl) {
-                self.start_level(level);
+        // While a key is being rebound every key press belongs to the
+        // binding, except back, which cancels it
+        if self.settings_menu.rebinding().is_some() {
+            self.menu.animate(dt);
+            if cmd.has(Action::Menu) {
+                self.settings_menu.update(&cmd, &mut self.settings);
+            } else {
+                self.capture_binding(rl);
             }
-            
-            // Music volume control in menu
-            if rl.is_key_pressed(KeyboardKey::KEY_MINUS) {
-                self.adjust_music_volume(-0.1);
+            self.audio.update(dt);
+            return;
+        }
+
+        // The screen's own input comes first, then the action keys it shares
+        // with the state machine rules
+        let state = self.states.current();
+        let transition = match state {
+            GameState::Title | GameState::Leaderboard => {
+                self.menu.animate(dt);
+                None
             }
-            if rl.is_key_pressed(KeyboardKey::KEY_EQUAL) {
-                self.adjust_music_volume(0.1);
+            GameState::LevelSelect => self.update_level_select(rl),
+            GameState::Playing => self.update_playing(&cmd, dt),
+            GameState::Paused => self.update_paused(rl, &cmd),
+            GameState::Settings => {
+                self.menu.animate(dt);
+                self.update_settings(rl, thread, &cmd)
             }
-            if rl.is_key_pressed(KeyboardKey::KEY_M) {
-                self.toggle_music();
+            _ => None,
+        };
+        if let Some(transition) = transition.or_else(|| state.on_command(&cmd)) {
+            self.apply_transition(transition);
+        }
+        sync_cursor(rl, self.states.current());
+
+        // Music controls, on every screen
+        if cmd.has(Action::VolumeDown) {
+            self.adjust_music_volume(-VOLUME_STEP);
+        }
+        if cmd.has(Action::VolumeUp) {
+            self.adjust_music_volume(VOLUME_STEP);
+        }
+        if cmd.has(Action::SfxVolumeDown) {
+            self.adjust_sfx_volume(-VOLUME_STEP);
+        }
+        if cmd.has(Action::SfxVolumeUp) {
+            self.adjust_sfx_volume(VOLUME_STEP);
+        }
+        if cmd.has(Action::ToggleMusic) {
+            self.toggle_music();
+        }
+        
+        self.audio.update(dt);
+    }
+
+    fn update_level_select(&mut self, rl: &mut RaylibHandle) -> Option<Transition> {
+        match self.menu.update(rl) {
+            Some(MenuEvent::Moved) => {
+                self.audio.play_sound(SoundEffect::MenuMove);
+                None
             }
-        } else {
-            self.player.update(rl, &self.map);
-            
-            // Check for coin collection (no sound)
-            if let Some((x, y)) = self.sprites.check_collision(&self.player) {
-                println!("Coin collected at ({:.1}, {:.1})!", x, y);
-            }
-            
-            if self.sprites.all_coins_collected() {
-                self.game_won = true;
-                println!("All coins collected! Level completed!");
-            }
-            
-            // Game controls
-            if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
-                self.in_menu = true;
-                self.game_won = false;
-            }
-            
-            if self.game_won && rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
-                self.in_menu = true;
-                self.game_won = false;
-            }
-            
-            if rl.is_key_pressed(KeyboardKey::KEY_R) {
+            Some(MenuEvent::Selected(_)) => {
+                self.audio.play_sound(SoundEffect::MenuSelect);
+                Some(Transition::Switch(GameState::Playing))
+            }
+            None => None,
+        }
+    }
+
+    fn update_playing(&mut self, cmd: &PlayerCommand, dt: f32) -> Option<Transition> {
+        let mut transition = None;
+
+        if let Some((_, time)) = &mut self.hint {
+            *time -= dt;
+            if *time <= 0.0 {
+                self.hint = None;
+            }
+        }
+
+        for event in self.world.update(cmd, dt) {
+            self.audio.play_event(&event, &self.world.player, &self.world.map);
+            match event {
+                WorldEvent::CoinCollected(x, y) => {
+                    println!("Coin collected at ({:.1}, {:.1})!", x, y);
+                }
+                WorldEvent::HealthCollected(health) => {
+                    println!("Health pack! Health: {}", health);
+                }
+                WorldEvent::KeyCollected => {
+                    println!("Key collected!");
+                }
+                WorldEvent::LevelCompleted => {
+                    println!("All coins collected! Level completed!");
+                    transition = Some(Transition::Switch(GameState::LevelComplete));
+                }
+                WorldEvent::PlayerHurt(health) => {
+                    println!("Hit! Health: {}", health);
+                }
+                WorldEvent::PlayerDied => {
+                    println!("The player died");
+                    transition = Some(Transition::Switch(GameState::GameOver));
+                }
+                WorldEvent::DoorOpened(..) => {}
+                WorldEvent::DoorLocked => {
+                    self.hint = Some(("Locked: find a key", HINT_SECONDS));
+                }
+            }
+        }
+        self.audio.update_emitters(dt, &self.world.player, &self.world.map);
+
+        if cmd.has(Action::Restart) {
+            self.restart_level();
+        }
+
+        transition
+    }
+
+    // Nothing in the world moves while paused; only the menu takes input
+    fn update_paused(&mut self, rl: &RaylibHandle, cmd: &PlayerCommand) -> Option<Transition> {
+        let mouse = rl.get_mouse_position();
+        let origin = pause_menu_origin(rl.get_screen_width(), rl.get_screen_height());
+        let hovered = pause_item_at(origin, mouse.x as i32, mouse.y as i32);
+        if let Some(index) = hovered
+            && rl.get_mouse_delta() != Vector2::zero()
+        {
+            self.pause_menu.select(index);
+        }
+
+        let clicked = hovered.filter(|_| rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT));
+        let picked = match clicked {
+            Some(index) => Some(PAUSE_ITEMS[index]),
+            None => self.pause_menu.update(cmd),
+        };
+
+        match picked? {
+            PauseItem::Resume => Some(Transition::Pop),
+            PauseItem::Restart => {
                 self.restart_level();
+                Some(Transition::Pop)
             }
-            
-            // Music controls in game
-            if rl.is_key_pressed(KeyboardKey::KEY_MINUS) {
-                self.adjust_music_volume(-0.1);
+            PauseItem::Settings => Some(Transition::Push(GameState::Settings)),
+            PauseItem::Quit => Some(Transition::Switch(GameState::LevelSelect)),
+        }
+    }
+
+    fn update_settings(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, cmd: &PlayerCommand) -> Option<Transition> {
+        match self.settings_menu.update(cmd, &mut self.settings)? {
+            SettingsChange::Video => self.apply_video(rl, thread),
+            SettingsChange::Camera => self.settings.configure_player(&mut self.world.player),
+            SettingsChange::Audio => {
+                self.audio.set_music_volume(self.settings.music_volume);
+                self.audio.set_sfx_volume(self.settings.sfx_volume);
             }
-            if rl.is_key_pressed(KeyboardKey::KEY_EQUAL) {
-                self.adjust_music_volume(0.1);
+            SettingsChange::Keys => self.keys = KeyMap::from_settings(&self.settings),
+            SettingsChange::Back => return Some(Transition::Pop),
+        }
+        None
+    }
+
+    // Binds the first key pressed that has a name in the settings file
+    fn capture_binding(&mut self, rl: &mut RaylibHandle) {
+        let Some(name) = rl.get_key_pressed().and_then(controls::key_name) else {
+            return;
+        };
+        if self.settings_menu.finish_rebind(&mut self.settings, name).is_some() {
+            self.keys = KeyMap::from_settings(&self.settings);
+        }
+    }
+
+    fn apply_video(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) {
+        rl.set_window_size(self.settings.width as i32, self.settings.height as i32);
+        if rl.is_window_fullscreen() != self.settings.fullscreen {
+            rl.toggle_fullscreen();
+        }
+        self.resize_renderer(rl, thread);
+    }
+
+    // The 3D view renders at the render scale of the window size and is
+    // stretched over the window when presented
+    fn resize_renderer(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) {
+        let (width, height) = self
+            .settings
+            .render_size(rl.get_screen_width() as u32, rl.get_screen_height() as u32);
+        let frame = self.raycaster.framebuffer();
+        if (frame.width, frame.height) == (width, height) {
+            return;
+        }
+
+        println!("Rendering at {}x{}", width, height);
+        self.raycaster.resize(width, height);
+        self.display.init(rl, thread, width, height);
+    }
+
+    fn apply_transition(&mut self, transition: Transition) {
+        if let Some((from, to)) = self.states.apply(transition) {
+            self.exit_state(from, to);
+            self.enter_state(to, Some(from));
+        }
+    }
+
+    // `from` is `None` for the title screen shown at startup
+    fn enter_state(&mut self, state: GameState, from: Option<GameState>) {
+        match state {
+            GameState::Title | GameState::LevelSelect => {
+                self.record_attempt();
+                self.audio.clear_emitters();
+                self.audio.play_playlist(&self.menu_music);
             }
-            if rl.is_key_pressed(KeyboardKey::KEY_M) {
-                self.toggle_music();
+            GameState::Playing => match from {
+                Some(GameState::Paused) => {}
+                Some(GameState::GameOver) => self.restart_level(),
+                _ => self.start_level(self.menu.selected_level()),
+            },
+            GameState::Paused if from == Some(GameState::Playing) => {
+                self.pause_menu.reset();
+
+                // Only pause music that is playing, so leaving the pause
+                // screen doesn't turn on music the player switched off
+                self.music_paused = self.audio.is_music_playing();
+                if self.music_paused {
+                    self.audio.pause_music();
+                }
             }
-            
-            self.sprites.update_animation(rl.get_frame_time());
+            GameState::LevelComplete => {
+                self.audio.play_sound(SoundEffect::LevelComplete);
+                self.record_completion();
+            }
+            _ => {}
+        }
+    }
+
+    fn exit_state(&mut self, state: GameState, to: GameState) {
+        if state == GameState::Settings {
+            self.save_settings();
+        }
+
+        // Settings open on top of the pause screen, which stays paused
+        if state == GameState::Paused && to != GameState::Settings && self.music_paused {
+            self.audio.resume_music();
+            self.music_paused = false;
         }
-        
-        self.audio.update();
     }
 
     pub fn draw(&mut self, d: &mut RaylibDrawHandle) {
-        if self.in_menu {
-            self.menu.draw(d);
-            
-            // Draw music controls
-            d.draw_text("Music Controls:", 10, 650, 16, Color::WHITE);
-            d.draw_text("M: Toggle Music", 10, 670, 14, Color::LIGHTGRAY);
-            d.draw_text("-/+: Volume", 10, 690, 14, Color::LIGHTGRAY);
-            
-            let status = if self.audio.is_music_playing() { "Playing" } else { "Stopped" };
-            d.draw_text(&format!("Music: {}", status), 10, 710, 14, Color::LIGHTGRAY);
-        } else {
-            self.raycaster.render(d, &self.player, &self.map, &self.sprites);
-            self.minimap.draw(d, &self.player, &self.map, &self.sprites);
-            
-            // Game UI
-            d.draw_text(&format!("Coins: {}/{}", self.sprites.coins_collected(), self.sprites.total_coins()), 10, 10, 20, Color::WHITE);
-            d.draw_text(&format!("Level: {}", self.current_level + 1), 10, 40, 20, Color::WHITE);
-            d.draw_text("ESC: Menu  R: Restart  M: Music", 10, 70, 20, Color::WHITE);
-            d.draw_text(&format!("FPS: {}", d.get_fps()), SCREEN_WIDTH - 120, 10, 20, Color::WHITE);
-            
-            if self.game_won {
-                d.draw_text("LEVEL COMPLETED!", 350, 300, 40, Color::GOLD);
-                d.draw_text("Press ENTER to return to menu", 350, 350, 20, Color::WHITE);
+        match self.states.current() {
+            GameState::Title => self.menu.draw_title(d, &self.settings),
+            GameState::LevelSelect => {
+                self.menu.draw(d, &self.settings);
+
+                let bottom = d.get_screen_height();
+                let status = if self.audio.is_music_playing() { "Playing" } else { "Stopped" };
+                d.draw_text(&format!("Music: {}", status), 10, bottom - 58, 14, Color::LIGHTGRAY);
+
+                let screen_width = d.get_screen_width();
+                draw_volume_indicator(d, screen_width - 190, 10, &self.audio);
+            }
+            GameState::Playing => self.draw_world(d),
+            GameState::Paused => self.draw_pause_menu(d),
+            GameState::LevelComplete => {
+                self.draw_world(d);
+                self.draw_level_complete(d);
             }
+            GameState::GameOver => {
+                self.draw_world(d);
+                let retry = format!("{}: Try again", self.settings.key(Control::Restart));
+                draw_overlay(d, "GAME OVER", Color::RED, &[&retry, "ENTER: Level select"]);
+            }
+            GameState::Settings => self.draw_settings(d),
+            GameState::Leaderboard => self.draw_leaderboard(d),
         }
     }
 
-    fn start_level(&mut self, level: usize) {
-        self.current_level = level;
-        self.map = Map::new(level);
-        self.sprites = SpriteManager::new(&self.map);
+    fn draw_world(&mut self, d: &mut RaylibDrawHandle) {
+        let world = &self.world;
+        let frame = self.raycaster.render(&world.player, &world.map, &world.sprites);
+        self.display.present(d, frame);
+        self.minimap.draw(d, &world.player, &world.map, &world.sprites);
         
-        // Set player starting position based on level
-        match level {
-            0 => self.player = Player::new(1.5, 1.5, 0.0),
-            1 => self.player = Player::new(1.5, 1.5, 0.0),
-            2 => self.player = Player::new(1.5, 1.5, 0.0),
-            _ => self.player = Player::new(1.5, 1.5, 0.0),
+        // Red flash that fades over the invulnerability after a hit
+        if world.player.invulnerable > 0.0 {
+            let alpha = (world.player.invulnerable / HURT_FLASH_SECONDS).min(1.0) * 120.0;
+            d.draw_rectangle(0, 0, d.get_screen_width(), d.get_screen_height(), Color::new(200, 0, 0, alpha as u8));
+        }
+
+        // Game UI
+        d.draw_text(&format!("Coins: {}/{}", world.sprites.coins_collected(), world.sprites.total_coins()), 10, 10, 20, Color::WHITE);
+        d.draw_text(&format!("Level: {}", world.current_level + 1), 10, 40, 20, Color::WHITE);
+        if world.keys > 0 {
+            d.draw_text(&format!("Keys: {}", world.keys), 200, 10, 20, Color::SKYBLUE);
+        }
+        let settings = &self.settings;
+        let actions = format!(
+            "{}: Pause  {}: Restart  {}: Use  {}: Music",
+            settings.key(Control::Pause),
+            settings.key(Control::Restart),
+            settings.key(Control::Use),
+            settings.key(Control::ToggleMusic),
+        );
+        let volume = format!(
+            "{}: Volume  {}: Effects",
+            settings.key_list(&[Control::VolumeDown, Control::VolumeUp]),
+            settings.key_list(&[Control::SfxVolumeDown, Control::SfxVolumeUp]),
+        );
+        d.draw_text(&actions, 10, 70, 16, Color::WHITE);
+        d.draw_text(&volume, 10, 90, 16, Color::WHITE);
+        let screen_width = d.get_screen_width();
+        if let Some((hint, _)) = self.hint {
+            let y = d.get_screen_height() / 2 + 40;
+            d.draw_text(hint, screen_width / 2 - menu::measure_text(hint, 24) / 2, y, 24, Color::YELLOW);
+        }
+        let time = progress::format_time(world.elapsed);
+        d.draw_text(&time, screen_width / 2 - menu::measure_text(&time, 28) / 2, 10, 28, Color::WHITE);
+        d.draw_text(&format!("FPS: {}", d.get_fps()), screen_width - 120, 10, 20, Color::WHITE);
+        draw_volume_indicator(d, screen_width - 190, 40, &self.audio);
+        draw_health_bar(d, 10, d.get_screen_height() - 40, world.player.health);
+    }
+
+    fn draw_level_complete(&self, d: &mut RaylibDrawHandle) {
+        let mut lines = Vec::new();
+        if let Some((run, rank)) = self.last_run {
+            lines.push(format!("Time {}   Coins {}   Score {}", progress::format_time(run.time), run.coins, run.score));
+            lines.push(match rank {
+                Some(rank) => format!("Rank #{} on this level", rank + 1),
+                None => format!("Not in the top {}", leaderboard::LEADERBOARD_SIZE),
+            });
+        }
+        lines.push("Press ENTER to return to menu".to_string());
+        let lines: Vec<&str> = lines.iter().map(|line| line.as_str()).collect();
+        draw_overlay(d, "LEVEL COMPLETED!", Color::GOLD, &lines);
+
+        if let Some(id) = self.level_ids.get(self.world.current_level) {
+            let rank = self.last_run.and_then(|(_, rank)| rank);
+            let top = d.get_screen_height() / 2 + 50;
+            draw_runs(d, top, self.leaderboard.runs(id), rank);
+        }
+    }
+
+    fn draw_leaderboard(&self, d: &mut RaylibDrawHandle) {
+        self.menu.draw_background(d);
+
+        let level = self.menu.selected_level();
+        let title = format!("Best runs: {}", self.menu.level_name(level).unwrap_or("?"));
+        let width = d.get_screen_width();
+        d.draw_text(&title, width / 2 - menu::measure_text(&title, 28) / 2, 240, 28, Color::WHITE);
+
+        match self.level_ids.get(level).map(|id| self.leaderboard.runs(id)) {
+            Some(runs) if !runs.is_empty() => draw_runs(d, 290, runs, None),
+            _ => {
+                let empty = "No runs yet";
+                d.draw_text(empty, width / 2 - menu::measure_text(empty, 20) / 2, 290, 20, Color::LIGHTGRAY);
+            }
+        }
+
+        let hint = format!("{} / ENTER: Back", self.settings.key(Control::Pause));
+        d.draw_text(&hint, width / 2 - menu::measure_text(&hint, 18) / 2, d.get_screen_height() - 60, 18, Color::LIGHTGRAY);
+    }
+
+    // The frozen level, dimmed, under the pause menu
+    fn draw_pause_menu(&self, d: &mut RaylibDrawHandle) {
+        self.display.redraw(d);
+        draw_overlay(d, "PAUSED", Color::WHITE, &[]);
+
+        let (x, top) = pause_menu_origin(d.get_screen_width(), d.get_screen_height());
+        for (i, item) in PAUSE_ITEMS.iter().enumerate() {
+            let y = top + i as i32 * PAUSE_ITEM_SPACING;
+            let color = if i == self.pause_menu.selected() {
+                d.draw_text(">", x - 30, y, PAUSE_ITEM_SIZE, Color::YELLOW);
+                Color::YELLOW
+            } else {
+                Color::WHITE
+            };
+            d.draw_text(item.label(), x, y, PAUSE_ITEM_SIZE, color);
+        }
+
+        let hint = format!("↑↓/Mouse: Select  ENTER/Click: Confirm  {}: Resume", self.settings.key(Control::Pause));
+        let hint_y = top + PAUSE_ITEMS.len() as i32 * PAUSE_ITEM_SPACING + 20;
+        d.draw_text(&hint, d.get_screen_width() / 2 - menu::measure_text(&hint, 18) / 2, hint_y, 18, Color::LIGHTGRAY);
+    }
+
+    fn draw_settings(&self, d: &mut RaylibDrawHandle) {
+        self.menu.draw_background(d);
+
+        let (width, height) = (d.get_screen_width(), d.get_screen_height());
+        let left = width / 2 - 212;
+        d.draw_text("Settings", left, SETTINGS_TOP - 50, 32, Color::WHITE);
+
+        let rows = self.settings_menu.rows();
+        let selected = self.settings_menu.selected();
+        let fits = ((height - SETTINGS_TOP - 80) / SETTINGS_ROW_HEIGHT).max(1) as usize;
+        let visible = fits.min(SETTINGS_VISIBLE_ROWS);
+        let first = selected
+            .saturating_sub(visible / 2)
+            .min(rows.len().saturating_sub(visible));
+
+        for (i, row) in rows.iter().enumerate().skip(first).take(visible) {
+            let y = SETTINGS_TOP + (i - first) as i32 * SETTINGS_ROW_HEIGHT;
+            let color = if i == selected { Color::YELLOW } else { Color::WHITE };
+            if i == selected {
+                d.draw_text(">", left - 30, y, 22, Color::YELLOW);
+            }
+            d.draw_text(row.label(), left, y, 22, color);
+
+            let value = match self.settings_menu.rebinding() {
+                Some(_) if i == selected => "Press a key...".to_string(),
+                _ => row.value(&self.settings),
+            };
+            d.draw_text(&value, left + 300, y, 22, color);
+        }
+
+        let hint = format!("↑↓: Select  ←→: Change  ENTER: Toggle / rebind  {}: Back", self.settings.key(Control::Pause));
+        d.draw_text(&hint, width / 2 - menu::measure_text(&hint, 18) / 2, height - 60, 18, Color::LIGHTGRAY);
+    }
+
+    fn start_level(&mut self, level: usize) {
+        self.record_attempt();
+        self.world = World::new(level, self.world.sprites.sparkle_lifetime);
+        self.hint = None;
+        self.settings.configure_player(&mut self.world.player);
+
+        if self.world.map.music.is_empty() {
+            self.audio.play_playlist(&self.menu_music);
+        } else {
+            self.audio.play_playlist(&self.world.map.music);
+        }
+
+        self.audio.clear_emitters();
+        let coins = self.world.map.get_coin_positions();
+        for (i, (x, y)) in coins.iter().enumerate() {
+            let phase = i as f32 / coins.len() as f32;
+            self.audio.add_emitter(SoundEffect::CoinPing, *x, *y, COIN_PING_VOLUME, COIN_PING_INTERVAL, phase);
         }
-        
-        self.game_won = false;
-        self.in_menu = false;
         
         println!("Starting level {}", level + 1);
     }
 
     fn restart_level(&mut self) {
-        self.start_level(self.current_level);
+        self.start_level(self.world.current_level);
+    }
+
+    fn record_completion(&mut self) {
+        self.last_run = None;
+        let Some(id) = self.level_ids.get(self.world.current_level).cloned() else {
+            return;
+        };
+        let coins = self.world.sprites.coins_collected();
+        if self.progress.record_completion(&id, self.world.elapsed, coins) {
+            println!("New best for {}: {}", id, progress::format_time(self.world.elapsed));
+            self.save_progress();
+        }
+
+        let run = Run::new(self.world.elapsed, coins);
+        let rank = self.leaderboard.insert(&id, run);
+        if rank.is_some()
+            && let Err(e) = self.leaderboard.save(leaderboard::LEADERBOARD_PATH.as_ref())
+        {
+            println!("Failed to save leaderboard to {}: {}", leaderboard::LEADERBOARD_PATH, e);
+        }
+        self.last_run = Some((run, rank));
+    }
+
+    // Keeps the coins of a level that is left or restarted before the end
+    fn record_attempt(&mut self) {
+        let Some(id) = self.level_ids.get(self.world.current_level) else {
+            return;
+        };
+        if self.progress.record_attempt(id, self.world.sprites.coins_collected()) {
+            self.save_progress();
+        }
+    }
+
+    fn save_progress(&mut self) {
+        if let Err(e) = self.progress.save(progress::PROGRESS_PATH.as_ref()) {
+            println!("Failed to save progress to {}: {}", progress::PROGRESS_PATH, e);
+        }
+        self.refresh_menu_progress();
+    }
+
+    fn refresh_menu_progress(&mut self) {
+        let saved = self.level_ids.iter().map(|id| self.progress.level(id).cloned()).collect();
+        self.menu.set_progress(saved);
     }
     
     fn adjust_music_volume(&mut self, delta: f32) {
-        // This is a simplified volume control - you might want to store the current volume
-        // For now, we'll just print what we're trying to do
-        if delta > 0.0 {
-            println!("Increasing music volume");
-        } else {
-            println!("Decreasing music volume");
+        self.settings.music_volume = self.audio.step_music_volume(delta);
+        self.save_settings();
+    }
+
+    fn adjust_sfx_volume(&mut self, delta: f32) {
+        self.settings.sfx_volume = self.audio.step_sfx_volume(delta);
+        self.save_settings();
+    }
+
+    fn save_settings(&self) {
+        if let Err(e) = self.settings.save(settings::SETTINGS_PATH.as_ref()) {
+            println!("Failed to save settings to {}: {}", settings::SETTINGS_PATH, e);
         }
-        // The actual volume adjustment would need to be implemented based on your audio system
     }
     
     fn toggle_music(&mut self) {
@@ -187,3 +626,87 @@ impl Game {
         }
     }
 }
+
+// Darkens the frame and shows a centered title with lines of help under it
+fn draw_overlay(d: &mut RaylibDrawHandle, title: &str, color: Color, lines: &[&str]) {
+    let (width, height) = (d.get_screen_width(), d.get_screen_height());
+    d.draw_rectangle(0, 0, width, height, Color::new(0, 0, 0, 150));
+
+    let top = height / 2 - 84;
+    d.draw_text(title, width / 2 - menu::measure_text(title, 40) / 2, top, 40, color);
+    for (i, line) in lines.iter().enumerate() {
+        d.draw_text(line, width / 2 - menu::measure_text(line, 20) / 2, top + 50 + i as i32 * 25, 20, Color::WHITE);
+    }
+}
+
+// A leaderboard table centred on the screen, with the row at `highlight` in
+// a different colour
+fn draw_runs(d: &mut RaylibDrawHandle, top: i32, runs: &[Run], highlight: Option<usize>) {
+    let left = d.get_screen_width() / 2 - 150;
+
+    for (i, run) in runs.iter().enumerate() {
+        let y = top + i as i32 * 20;
+        let color = if highlight == Some(i) { Color::YELLOW } else { Color::WHITE };
+        d.draw_text(&format!("{:>2}.", i + 1), left, y, 18, color);
+        d.draw_text(&run.score.to_string(), left + 40, y, 18, color);
+        d.draw_text(&progress::format_time(run.time), left + 130, y, 18, color);
+        d.draw_text(&format!("{} coins", run.coins), left + 220, y, 18, color);
+    }
+}
+
+// Top-left corner of the pause menu list in a window of the given size
+fn pause_menu_origin(width: i32, height: i32) -> (i32, i32) {
+    (width / 2 - PAUSE_ITEM_WIDTH / 2, height / 2 - 24)
+}
+
+// The pause menu row under the mouse, if any
+fn pause_item_at(origin: (i32, i32), x: i32, y: i32) -> Option<usize> {
+    let (left, top) = origin;
+    if !(left..left + PAUSE_ITEM_WIDTH).contains(&x) || y < top {
+        return None;
+    }
+    let index = ((y - top) / PAUSE_ITEM_SPACING) as usize;
+    (index < PAUSE_ITEMS.len()).then_some(index)
+}
+
+// Mouse look while playing, a free cursor on every other screen
+fn sync_cursor(rl: &mut RaylibHandle, state: GameState) {
+    let playing = state == GameState::Playing;
+    if playing && !rl.is_cursor_hidden() {
+        rl.disable_cursor();
+    } else if !playing && rl.is_cursor_hidden() {
+        rl.enable_cursor();
+    }
+}
+
+// Music and effects volume as two small bars with percentages
+fn draw_volume_indicator(d: &mut RaylibDrawHandle, x: i32, y: i32, audio: &AudioManager) {
+    let bar_width = 100;
+    let volumes = [("Music", audio.music_volume()), ("SFX", audio.sfx_volume())];
+
+    for (i, (label, volume)) in volumes.iter().enumerate() {
+        let row_y = y + i as i32 * 18;
+        d.draw_text(label, x, row_y, 14, Color::LIGHTGRAY);
+        d.draw_rectangle(x + 45, row_y + 2, bar_width, 10, Color::DARKGRAY);
+        d.draw_rectangle(x + 45, row_y + 2, (bar_width as f32 * volume) as i32, 10, Color::SKYBLUE);
+        d.draw_text(&format!("{:.0}%", volume * 100.0), x + 45 + bar_width + 6, row_y, 14, Color::LIGHTGRAY);
+    }
+}
+
+// Health as a bar that turns from green to red as it runs out
+fn draw_health_bar(d: &mut RaylibDrawHandle, x: i32, y: i32, health: i32) {
+    let bar_width = 200;
+    let fraction = health as f32 / player::MAX_HEALTH as f32;
+    let color = if fraction > 0.5 {
+        Color::GREEN
+    } else if fraction > 0.25 {
+        Color::ORANGE
+    } else {
+        Color::RED
+    };
+
+    d.draw_text("HP", x, y, 20, Color::WHITE);
+    d.draw_rectangle(x + 35, y + 2, bar_width, 16, Color::DARKGRAY);
+    d.draw_rectangle(x + 35, y + 2, (bar_width as f32 * fraction) as i32, 16, color);
+    d.draw_text(&health.to_string(), x + 35 + bar_width + 8, y, 20, Color::WHITE);
+}
@@ -0,0 +1,189 @@
+use std::path::PathBuf;
+use crate::entity;
+use crate::framebuffer::FrameBuffer;
+use crate::level;
+use crate::map::Map;
+use crate::player::Player;
+use crate::raycast::RayCaster;
+use crate::sprite::SpriteManager;
+
+pub const USAGE: &str = "\
+Usage: proyecto --headless [options]
+
+Renders a single frame without opening a window and writes it as a PNG.
+
+Options:
+  --level N          level to render, as numbered in the menu (default 1)
+  --pose X,Y,ANGLE   player position and view angle in radians (default: level spawn)
+  --size WxH         output resolution (default 1024x768)
+  --out FILE         output PNG path (default frame.png)
+  --no-textures      use flat wall colors, independent of the image files";
+
+pub struct HeadlessOptions {
+    pub level: usize,
+    pub pose: Option<(f32, f32, f32)>,
+    pub width: usize,
+    pub height: usize,
+    pub output: PathBuf,
+    pub textures: bool,
+}
+
+impl HeadlessOptions {
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = Self {
+            level: 0,
+            pose: None,
+            width: 1024,
+            height: 768,
+            output: PathBuf::from("frame.png"),
+            textures: true,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => {}
+                "--no-textures" => options.textures = false,
+                "--level" | "--pose" | "--size" | "--out" => {
+                    let value = args.next().ok_or_else(|| format!("{} expects a value", arg))?;
+                    match arg.as_str() {
+                        "--level" => {
+                            options.level = value
+                                .parse::<usize>()
+                                .ok()
+                                .filter(|level| *level >= 1)
+                                .ok_or_else(|| format!("invalid level '{}'", value))?
+                                - 1;
+                        }
+                        "--pose" => {
+                            let numbers: Vec<f32> = value
+                                .split(',')
+                                .map(|n| n.trim().parse::<f32>())
+                                .collect::<Result<_, _>>()
+                                .map_err(|_| format!("invalid pose '{}'", value))?;
+                            if numbers.len() != 3 {
+                                return Err(format!("pose expects X,Y,ANGLE, got '{}'", value));
+                            }
+                            options.pose = Some((numbers[0], numbers[1], numbers[2]));
+                        }
+                        "--size" => {
+                            let (width, height) = value
+                                .split_once('x')
+                                .and_then(|(w, h)| Some((w.parse::<usize>().ok()?, h.parse::<usize>().ok()?)))
+                                .filter(|(w, h)| *w > 0 && *h > 0)
+                                .ok_or_else(|| format!("invalid size '{}', expected WxH", value))?;
+                            options.width = width;
+                            options.height = height;
+                        }
+                        _ => options.output = PathBuf::from(value),
+                    }
+                }
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+// Renders one frame of a level from the given pose (or the level's spawn)
+pub fn render_frame<'a>(raycaster: &'a mut RayCaster, map: &Map, pose: Option<(f32, f32, f32)>) -> &'a FrameBuffer {
+    // Nothing is picked up in a single frame, so sparkles never show
+    let sprites = SpriteManager::new(map, entity::SPARKLE_LIFETIME);
+    let (x, y, angle) = pose.unwrap_or(map.spawn);
+    let player = Player::new(x, y, angle);
+
+    raycaster.render(&player, map, &sprites)
+}
+
+// `raycaster` must have been created at the requested size, with whatever
+// textures the caller wants in the picture
+pub fn render_to_png(raycaster: &mut RayCaster, options: &HeadlessOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let levels = level::list_levels(level::LEVELS_DIR);
+    let Some(path) = levels.get(options.level) else {
+        return Err(format!("level {} not found, {} levels in {}", options.level + 1, levels.len(), level::LEVELS_DIR).into());
+    };
+    let map = Map::load(path)?;
+
+    let frame = render_frame(raycaster, &map, options.pose);
+    frame.save_png(&options.output)?;
+
+    println!("Wrote {}x{} frame of '{}' to {}", frame.width, frame.height, map.name, options.output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<HeadlessOptions, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        HeadlessOptions::from_args(&args)
+    }
+
+    #[test]
+    fn parses_every_option() {
+        let options = parse(&[
+            "--headless",
+            "--level",
+            "2",
+            "--pose",
+            "1.5, 2.5,0.25",
+            "--size",
+            "320x200",
+            "--out",
+            "shot.png",
+            "--no-textures",
+        ])
+        .unwrap();
+
+        assert_eq!(options.level, 1);
+        assert_eq!(options.pose, Some((1.5, 2.5, 0.25)));
+        assert_eq!((options.width, options.height), (320, 200));
+        assert_eq!(options.output, PathBuf::from("shot.png"));
+        assert!(!options.textures);
+    }
+
+    #[test]
+    fn defaults_without_options() {
+        let options = parse(&["--headless"]).unwrap();
+
+        assert_eq!(options.level, 0);
+        assert_eq!(options.pose, None);
+        assert_eq!((options.width, options.height), (1024, 768));
+        assert_eq!(options.output, PathBuf::from("frame.png"));
+        assert!(options.textures);
+    }
+
+    #[test]
+    fn rejects_bad_poses() {
+        assert_eq!(parse(&["--pose", "1,2"]).err().unwrap(), "pose expects X,Y,ANGLE, got '1,2'");
+        assert_eq!(parse(&["--pose", "1,2,3,4"]).err().unwrap(), "pose expects X,Y,ANGLE, got '1,2,3,4'");
+        assert_eq!(parse(&["--pose", "1,y,0"]).err().unwrap(), "invalid pose '1,y,0'");
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        for size in ["640", "640x", "x480", "0x480", "640x0", "-640x480", "640*480"] {
+            assert_eq!(
+                parse(&["--size", size]).err().unwrap(),
+                format!("invalid size '{}', expected WxH", size)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_levels() {
+        for level in ["0", "-1", "one"] {
+            assert_eq!(parse(&["--level", level]).err().unwrap(), format!("invalid level '{}'", level));
+        }
+    }
+
+    #[test]
+    fn rejects_missing_values_and_unknown_arguments() {
+        for option in ["--level", "--pose", "--size", "--out"] {
+            assert_eq!(parse(&["--headless", option]).err().unwrap(), format!("{} expects a value", option));
+        }
+        assert_eq!(parse(&["--fast"]).err().unwrap(), "unknown argument '--fast'");
+    }
+}
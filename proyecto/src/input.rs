@@ -0,0 +1,166 @@
+// What the player asked for during one frame, independent of where it came
+// from. The front-end fills one of these from the keyboard and mouse; tests
+// and replays can build them directly.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Restart,
+    Menu,
+    Confirm,
+    Settings,
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    Leaderboard,
+    ToggleMusic,
+    VolumeUp,
+    VolumeDown,
+    SfxVolumeUp,
+    SfxVolumeDown,
+    // Open the door in front of the player
+    Use,
+}
+
+// The keys the player can rebind: movement and the in-game actions. Menu
+// navigation (arrows, ENTER, TAB) stays fixed so a bad binding can always be
+// undone from the settings screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Control {
+    Forward,
+    Back,
+    StrafeLeft,
+    StrafeRight,
+    TurnLeft,
+    TurnRight,
+    Use,
+    Restart,
+    Pause,
+    ToggleMusic,
+    VolumeUp,
+    VolumeDown,
+    SfxVolumeUp,
+    SfxVolumeDown,
+}
+
+pub const CONTROLS: [Control; 14] = [
+    Control::Forward,
+    Control::Back,
+    Control::StrafeLeft,
+    Control::StrafeRight,
+    Control::TurnLeft,
+    Control::TurnRight,
+    Control::Use,
+    Control::Restart,
+    Control::Pause,
+    Control::ToggleMusic,
+    Control::VolumeUp,
+    Control::VolumeDown,
+    Control::SfxVolumeUp,
+    Control::SfxVolumeDown,
+];
+
+impl Control {
+    // Name in the settings file, after `key.`
+    pub fn config_name(self) -> &'static str {
+        match self {
+            Control::Forward => "forward",
+            Control::Back => "back",
+            Control::StrafeLeft => "strafe_left",
+            Control::StrafeRight => "strafe_right",
+            Control::TurnLeft => "turn_left",
+            Control::TurnRight => "turn_right",
+            Control::Use => "use",
+            Control::Restart => "restart",
+            Control::Pause => "pause",
+            Control::ToggleMusic => "toggle_music",
+            Control::VolumeUp => "volume_up",
+            Control::VolumeDown => "volume_down",
+            Control::SfxVolumeUp => "sfx_volume_up",
+            Control::SfxVolumeDown => "sfx_volume_down",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Control::Forward => "Move forward",
+            Control::Back => "Move back",
+            Control::StrafeLeft => "Strafe left",
+            Control::StrafeRight => "Strafe right",
+            Control::TurnLeft => "Turn left",
+            Control::TurnRight => "Turn right",
+            Control::Use => "Use / open door",
+            Control::Restart => "Restart level",
+            Control::Pause => "Pause / back",
+            Control::ToggleMusic => "Toggle music",
+            Control::VolumeUp => "Music volume up",
+            Control::VolumeDown => "Music volume down",
+            Control::SfxVolumeUp => "Effects volume up",
+            Control::SfxVolumeDown => "Effects volume down",
+        }
+    }
+
+    // Key names are raylib's `KEY_*` constants without the prefix
+    pub fn default_key(self) -> &'static str {
+        match self {
+            Control::Forward => "W",
+            Control::Back => "S",
+            Control::StrafeLeft => "A",
+            Control::StrafeRight => "D",
+            Control::TurnLeft => "LEFT",
+            Control::TurnRight => "RIGHT",
+            Control::Use => "E",
+            Control::Restart => "R",
+            Control::Pause => "ESCAPE",
+            Control::ToggleMusic => "M",
+            Control::VolumeUp => "EQUAL",
+            Control::VolumeDown => "MINUS",
+            Control::SfxVolumeUp => "RIGHT_BRACKET",
+            Control::SfxVolumeDown => "LEFT_BRACKET",
+        }
+    }
+
+    // The action a press of this control triggers; movement controls are
+    // held instead and have none
+    pub fn action(self) -> Option<Action> {
+        match self {
+            Control::Use => Some(Action::Use),
+            Control::Restart => Some(Action::Restart),
+            Control::Pause => Some(Action::Menu),
+            Control::ToggleMusic => Some(Action::ToggleMusic),
+            Control::VolumeUp => Some(Action::VolumeUp),
+            Control::VolumeDown => Some(Action::VolumeDown),
+            Control::SfxVolumeUp => Some(Action::SfxVolumeUp),
+            Control::SfxVolumeDown => Some(Action::SfxVolumeDown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerCommand {
+    // -1..=1, positive = forward, right, clockwise
+    pub forward: f32,
+    pub strafe: f32,
+    pub turn: f32,
+    // Horizontal mouse movement in pixels
+    pub mouse_dx: f32,
+    // Actions triggered this frame (key presses, not held keys)
+    pub actions: Vec<Action>,
+}
+
+impl PlayerCommand {
+    pub fn new(forward: f32, strafe: f32, turn: f32, mouse_dx: f32) -> Self {
+        Self {
+            forward,
+            strafe,
+            turn,
+            mouse_dx,
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn has(&self, action: Action) -> bool {
+        self.actions.contains(&action)
+    }
+}
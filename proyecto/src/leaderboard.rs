@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::config::{self, Entry};
+
+pub const LEADERBOARD_PATH: &str = "leaderboard.sav";
+pub const LEADERBOARD_VERSION: u32 = 1;
+// Runs kept per level
+pub const LEADERBOARD_SIZE: usize = 10;
+
+const COIN_POINTS: u32 = 1000;
+// Finishing faster than this earns a bonus for every second left over
+const PAR_SECONDS: f32 = 180.0;
+const POINTS_PER_SECOND: f32 = 20.0;
+
+// One completed run of a level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Run {
+    pub score: u32,
+    // Seconds
+    pub time: f32,
+    pub coins: usize,
+}
+
+impl Run {
+    pub fn new(time: f32, coins: usize) -> Self {
+        Self {
+            score: score(time, coins),
+            time,
+            coins,
+        }
+    }
+}
+
+// Every coin is worth the same, and the time bonus rewards finishing under par
+pub fn score(time: f32, coins: usize) -> u32 {
+    let bonus = ((PAR_SECONDS - time).max(0.0) * POINTS_PER_SECOND).round() as u32;
+    coins as u32 * COIN_POINTS + bonus
+}
+
+// Best runs per level, keyed by level file name like `Progress`, best first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Leaderboard {
+    levels: HashMap<String, Vec<Run>>,
+}
+
+impl Leaderboard {
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(source) => Self::parse(path, &source),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /*
+     * A version line, then one section per level file with a line per run:
+     *
+     *   version = 1
+     *
+     *   [level1.level]
+     *   run = 5940 42.37 3   # score, seconds, coins
+     */
+    pub fn parse(path: &Path, source: &str) -> Self {
+        let mut leaderboard = Self::default();
+        let mut section: Option<String> = None;
+        let path = path.display();
+
+        for line in config::lines(source) {
+            let (key, value) = match line.entry {
+                Entry::Section(name) => {
+                    leaderboard.levels.entry(name.to_string()).or_default();
+                    section = Some(name.to_string());
+                    continue;
+                }
+                Entry::Pair { key, value, .. } => (key, value),
+                Entry::Text => {
+                    println!("{}:{}: expected `key = value`", path, line.number);
+                    continue;
+                }
+            };
+
+            match (key, &section) {
+                ("version", None) => match value.parse::<u32>() {
+                    Ok(version) if version <= LEADERBOARD_VERSION => {}
+                    Ok(version) => {
+                        println!("{}: version {} is newer than this game ({}), ignoring it", path, version, LEADERBOARD_VERSION);
+                        return Self::default();
+                    }
+                    Err(_) => println!("{}:{}: invalid version '{}'", path, line.number, value),
+                },
+                ("run", Some(name)) => match parse_run(value) {
+                    Some(run) => leaderboard.levels.entry(name.clone()).or_default().push(run),
+                    None => println!("{}:{}: run expects SCORE SECONDS COINS, got '{}'", path, line.number, value),
+                },
+                _ => println!("{}:{}: unexpected key '{}'", path, line.number, key),
+            }
+        }
+
+        for runs in leaderboard.levels.values_mut() {
+            sort_runs(runs);
+            runs.truncate(LEADERBOARD_SIZE);
+        }
+        leaderboard
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut names: Vec<&String> = self.levels.keys().collect();
+        names.sort();
+
+        let mut text = format!("version = {}\n", LEADERBOARD_VERSION);
+        for name in names {
+            text.push_str(&format!("\n[{}]\n", name));
+            for run in &self.levels[name] {
+                text.push_str(&format!("run = {} {:.2} {}\n", run.score, run.time, run.coins));
+            }
+        }
+        text
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn runs(&self, name: &str) -> &[Run] {
+        self.levels.get(name).map_or(&[], |runs| runs.as_slice())
+    }
+
+    // Adds `run` to the level's table and returns its place (0 = best), or
+    // `None` when it didn't make the top `LEADERBOARD_SIZE`
+    pub fn insert(&mut self, name: &str, run: Run) -> Option<usize> {
+        let runs = self.levels.entry(name.to_string()).or_default();
+        let rank = runs
+            .iter()
+            .position(|other| ranks_above(&run, other))
+            .unwrap_or(runs.len());
+        if rank >= LEADERBOARD_SIZE {
+            return None;
+        }
+
+        runs.insert(rank, run);
+        runs.truncate(LEADERBOARD_SIZE);
+        Some(rank)
+    }
+}
+
+fn parse_run(value: &str) -> Option<Run> {
+    let mut fields = value.split_whitespace();
+    let run = Run {
+        score: fields.next()?.parse().ok()?,
+        time: fields.next()?.parse().ok()?,
+        coins: fields.next()?.parse().ok()?,
+    };
+    fields.next().is_none().then_some(run)
+}
+
+// Higher score first, the faster run on a tie
+fn ranks_above(run: &Run, other: &Run) -> bool {
+    run.score > other.score || (run.score == other.score && run.time < other.time)
+}
+
+fn sort_runs(runs: &mut [Run]) {
+    runs.sort_by(|a, b| b.score.cmp(&a.score).then(a.time.total_cmp(&b.time)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Leaderboard {
+        Leaderboard::parse(Path::new(LEADERBOARD_PATH), source)
+    }
+
+    fn run(score: u32, time: f32) -> Run {
+        Run { score, time, coins: 1 }
+    }
+
+    #[test]
+    fn scores_count_coins_and_time_under_par() {
+        assert_eq!(score(PAR_SECONDS, 3), 3000);
+        assert_eq!(score(PAR_SECONDS + 60.0, 3), 3000);
+        assert_eq!(score(PAR_SECONDS - 10.0, 0), 200);
+        assert_eq!(Run::new(PAR_SECONDS - 1.0, 2).score, 2020);
+    }
+
+    #[test]
+    fn leaderboard_survives_a_round_trip() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.insert("level1.level", Run::new(42.37, 3));
+        leaderboard.insert("level1.level", Run::new(61.5, 3));
+        leaderboard.insert("level2.level", Run::new(90.25, 6));
+
+        let loaded = parse(&leaderboard.to_text());
+        assert_eq!(loaded, leaderboard);
+        assert_eq!(loaded.runs("level1.level").len(), 2);
+        assert_eq!(loaded.runs("level3.level"), &[]);
+    }
+
+    #[test]
+    fn a_newer_or_damaged_file_falls_back_safely() {
+        let source = format!("version = {}\n[level1.level]\nrun = 100 1.0 1\n", LEADERBOARD_VERSION + 1);
+        assert_eq!(parse(&source), Leaderboard::default());
+
+        let leaderboard = parse(
+            "version = 0\nrun = 5 5.0 5\n[level1.level]\nrun = 100 1.0\nrun = 100 1.0 1 9\nrun = 300 2.0 1\nbest = 1\n",
+        );
+        assert_eq!(leaderboard.runs("level1.level"), &[run(300, 2.0)]);
+    }
+
+    #[test]
+    fn runs_rank_by_score_then_time() {
+        let mut leaderboard = Leaderboard::default();
+        assert_eq!(leaderboard.insert("level1.level", run(500, 30.0)), Some(0));
+        assert_eq!(leaderboard.insert("level1.level", run(800, 50.0)), Some(0));
+        // The same score, faster
+        assert_eq!(leaderboard.insert("level1.level", run(500, 20.0)), Some(1));
+        // A full tie goes below the run that got there first
+        assert_eq!(leaderboard.insert("level1.level", run(500, 30.0)), Some(3));
+
+        assert_eq!(
+            leaderboard.runs("level1.level"),
+            &[run(800, 50.0), run(500, 20.0), run(500, 30.0), run(500, 30.0)]
+        );
+
+        // Loading sorts the same way
+        let loaded = parse("[level1.level]\nrun = 500 30.0 1\nrun = 500 20.0 1\nrun = 800 50.0 1\n");
+        assert_eq!(loaded.runs("level1.level"), &[run(800, 50.0), run(500, 20.0), run(500, 30.0)]);
+    }
+
+    #[test]
+    fn only_the_best_runs_are_kept() {
+        let mut leaderboard = Leaderboard::default();
+        for i in 0..LEADERBOARD_SIZE as u32 {
+            assert_eq!(leaderboard.insert("level1.level", run(1000 - i * 10, 10.0)), Some(i as usize));
+        }
+        // Below every kept run
+        assert_eq!(leaderboard.insert("level1.level", run(1, 10.0)), None);
+        // Pushes the last one out
+        assert_eq!(leaderboard.insert("level1.level", run(995, 10.0)), Some(1));
+
+        let runs = leaderboard.runs("level1.level");
+        assert_eq!(runs.len(), LEADERBOARD_SIZE);
+        assert_eq!(runs.last(), Some(&run(1000 - (LEADERBOARD_SIZE as u32 - 2) * 10, 10.0)));
+
+        let mut source = String::from("[level1.level]\n");
+        for i in 0..LEADERBOARD_SIZE + 5 {
+            source.push_str(&format!("run = {} 10.0 1\n", i));
+        }
+        let loaded = parse(&source);
+        assert_eq!(loaded.runs("level1.level").len(), LEADERBOARD_SIZE);
+        assert_eq!(loaded.runs("level1.level")[0].score, LEADERBOARD_SIZE as u32 + 4);
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config::{self, Entry};
+use crate::door::{self, DoorKind};
+
+pub const LEVELS_DIR: &str = "assets/levels";
+const LEVEL_EXTENSION: &str = "level";
+
+// Everything a level file can describe, before it is turned into a `Map`
+pub struct LevelFile {
+    pub name: String,
+    pub data: Vec<Vec<u8>>,
+    pub spawn: (f32, f32, f32),
+    pub entities: Vec<Placement>,
+    // Cell and kind of every door in the grid
+    pub doors: Vec<(usize, usize, DoorKind)>,
+    pub textures: HashMap<u8, String>,
+    // Played in order while the level is running, empty to keep the menu music
+    pub music: Vec<String>,
+}
+
+// Something placed in a level besides the walls, which becomes an entity
+// when the level starts
+#[derive(Debug, Clone, PartialEq)]
+pub enum Placement {
+    Coin(f32, f32),
+    // Patrol route; the enemy starts on the first point
+    Enemy(Vec<(f32, f32)>),
+    HealthPack(f32, f32),
+    Key(f32, f32),
+    // Sprite path, drawn at the position
+    Decoration(String, f32, f32),
+}
+
+impl Placement {
+    // Every position the placement uses, to check they are all open cells
+    pub fn points(&self) -> Vec<(f32, f32)> {
+        match self {
+            Placement::Coin(x, y)
+            | Placement::HealthPack(x, y)
+            | Placement::Key(x, y)
+            | Placement::Decoration(_, x, y) => vec![(*x, *y)],
+            Placement::Enemy(route) => route.clone(),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Placement::Coin(..) => "coin",
+            Placement::Enemy(_) => "enemy route",
+            Placement::HealthPack(..) => "health pack",
+            Placement::Key(..) => "key",
+            Placement::Decoration(..) => "decoration",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LevelError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.path.display(), self.message)
+        } else {
+            write!(f, "{}:{}:{}: {}", self.path.display(), self.line, self.column, self.message)
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+// Returns every level file in `dir`, sorted by file name so that
+// `level1.level`, `level2.level`, ... keep their order in the menu
+pub fn list_levels(dir: &str) -> Vec<PathBuf> {
+    let mut levels = Vec::new();
+
+    match fs::read_dir(dir) {
+        Ok(entries) => {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|ext| ext == LEVEL_EXTENSION) {
+                    levels.push(path);
+                }
+            }
+        }
+        Err(e) => println!("Failed to read level directory {}: {}", dir, e),
+    }
+
+    levels.sort();
+    levels
+}
+
+// A level file and what loading it gave
+pub type LoadedLevel = (PathBuf, Result<LevelFile, LevelError>);
+
+// Every level in `dir`, in `list_levels` order, each read and parsed once so
+// the lists below don't go back to the disk
+pub fn load_levels(dir: &str) -> Vec<LoadedLevel> {
+    list_levels(dir)
+        .into_iter()
+        .map(|path| {
+            let level = load_level(&path);
+            (path, level)
+        })
+        .collect()
+}
+
+// Display names for the menu, one per level
+pub fn level_names(levels: &[LoadedLevel]) -> Vec<String> {
+    levels
+        .iter()
+        .map(|(path, level)| match level {
+            Ok(level) => level.name.clone(),
+            Err(e) => {
+                println!("Failed to load level: {}", e);
+                path.display().to_string()
+            }
+        })
+        .collect()
+}
+
+// File names, one per level, to tell levels apart in saves
+pub fn level_ids(levels: &[LoadedLevel]) -> Vec<String> {
+    levels
+        .iter()
+        .map(|(path, _)| {
+            path.file_name()
+                .map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned())
+        })
+        .collect()
+}
+
+// Every texture path referenced by the levels, without duplicates
+pub fn texture_paths(levels: &[LoadedLevel]) -> Vec<String> {
+    unique_sorted(parsed(levels).flat_map(|level| level.textures.values().cloned()))
+}
+
+// Every decoration sprite referenced by the levels, without duplicates
+pub fn sprite_paths(levels: &[LoadedLevel]) -> Vec<String> {
+    unique_sorted(parsed(levels).flat_map(|level| {
+        level.entities.iter().filter_map(|entity| match entity {
+            Placement::Decoration(sprite, ..) => Some(sprite.clone()),
+            _ => None,
+        })
+    }))
+}
+
+// Every music track referenced by the levels, without duplicates
+pub fn music_paths(levels: &[LoadedLevel]) -> Vec<String> {
+    unique_sorted(parsed(levels).flat_map(|level| level.music.iter().cloned()))
+}
+
+// The levels that loaded; the others are reported by `level_names`
+fn parsed(levels: &[LoadedLevel]) -> impl Iterator<Item = &LevelFile> {
+    levels.iter().filter_map(|(_, level)| level.as_ref().ok())
+}
+
+fn unique_sorted(paths: impl Iterator<Item = String>) -> Vec<String> {
+    let mut paths: Vec<String> = paths.collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+pub fn load_level(path: &Path) -> Result<LevelFile, LevelError> {
+    let source = fs::read_to_string(path).map_err(|e| LevelError {
+        path: path.to_path_buf(),
+        line: 0,
+        column: 0,
+        message: e.to_string(),
+    })?;
+
+    parse_level(path, &source)
+}
+
+/*
+ * Level files are plain text: a header of `key = value` lines followed by
+ * a `[grid]` section with one digit per cell, 0 for floor and 1-9 for wall
+ * types, or a door letter: `D` opens with the use key, `A` automatically and
+ * `L` needs a key. `#` starts a comment.
+ *
+ *   name = The Beginning
+ *   spawn = 1.5 1.5 0.0
+ *   coin = 12.5 3.5
+ *   enemy = 5.5 2.5 8.5 2.5   # patrol route, one `x y` pair per point
+ *   health = 2.5 6.5
+ *   key = 9.5 9.5
+ *   decoration = 4.5 4.5 assets/sprites/barrel.png
+ *   texture 1 = assets/textures/dungeon.jpg
+ *   texture door = assets/textures/door.jpg
+ *   music = assets/music/dungeon.ogg
+ *
+ *   [grid]
+ *   11111
+ *   10D01
+ *   11111
+ */
+pub fn parse_level(path: &Path, source: &str) -> Result<LevelFile, LevelError> {
+    let error = |line: usize, column: usize, message: String| LevelError {
+        path: path.to_path_buf(),
+        line,
+        column,
+        message,
+    };
+
+    let mut name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut spawn = None;
+    let mut entities = Vec::new();
+    let mut doors = Vec::new();
+    let mut textures = HashMap::new();
+    let mut music = Vec::new();
+    let mut data: Vec<Vec<u8>> = Vec::new();
+    let mut in_grid = false;
+
+    // Positions are validated once the grid size is known
+    let mut spawn_at = (0, 0);
+    let mut entities_at = Vec::new();
+
+    for line in config::lines(source) {
+        let line_no = line.number;
+
+        if line.entry == Entry::Section("grid") {
+            if in_grid {
+                return Err(error(line_no, line.column, "duplicate [grid] section".to_string()));
+            }
+            in_grid = true;
+            continue;
+        }
+
+        if in_grid {
+            let mut row = Vec::new();
+            for (offset, ch) in line.text.chars().enumerate() {
+                if let Some(cell) = ch.to_digit(10) {
+                    row.push(cell as u8);
+                } else if let Some(kind) = DoorKind::from_grid(ch) {
+                    doors.push((row.len(), data.len(), kind, (line_no, line.column + offset)));
+                    row.push(door::DOOR_CELL);
+                } else {
+                    return Err(error(
+                        line_no,
+                        line.column + offset,
+                        format!("invalid cell '{}', expected a digit 0-9 or a door D, A or L", ch),
+                    ));
+                }
+            }
+            if let Some(first) = data.first()
+                && row.len() != first.len()
+            {
+                return Err(error(
+                    line_no,
+                    line.column,
+                    format!("row has {} cells, expected {}", row.len(), first.len()),
+                ));
+            }
+            data.push(row);
+            continue;
+        }
+
+        let Entry::Pair { key, value, value_column } = line.entry else {
+            return Err(error(line_no, line.column, "expected `key = value` or [grid]".to_string()));
+        };
+
+        let mut key_parts = key.split_whitespace();
+        match (key_parts.next(), key_parts.next(), key_parts.next()) {
+            (Some("name"), None, _) => name = value.to_string(),
+            (Some("spawn"), None, _) => {
+                let numbers = parse_numbers(value)
+                    .filter(|n| n.len() == 2 || n.len() == 3)
+                    .ok_or_else(|| error(line_no, value_column, "spawn expects `x y [angle]`".to_string()))?;
+                spawn = Some((numbers[0], numbers[1], numbers.get(2).copied().unwrap_or(0.0)));
+                spawn_at = (line_no, value_column);
+            }
+            (Some("coin"), None, _) => {
+                let numbers = parse_numbers(value)
+                    .filter(|n| n.len() == 2)
+                    .ok_or_else(|| error(line_no, value_column, "coin expects `x y`".to_string()))?;
+                entities.push(Placement::Coin(numbers[0], numbers[1]));
+                entities_at.push((line_no, value_column));
+            }
+            (Some("enemy"), None, _) => {
+                let numbers = parse_numbers(value)
+                    .filter(|n| !n.is_empty() && n.len() % 2 == 0)
+                    .ok_or_else(|| error(line_no, value_column, "enemy expects `x y [x y ...]`".to_string()))?;
+                entities.push(Placement::Enemy(numbers.chunks(2).map(|point| (point[0], point[1])).collect()));
+                entities_at.push((line_no, value_column));
+            }
+            (Some("health"), None, _) => {
+                let numbers = parse_numbers(value)
+                    .filter(|n| n.len() == 2)
+                    .ok_or_else(|| error(line_no, value_column, "health expects `x y`".to_string()))?;
+                entities.push(Placement::HealthPack(numbers[0], numbers[1]));
+                entities_at.push((line_no, value_column));
+            }
+            (Some("key"), None, _) => {
+                let numbers = parse_numbers(value)
+                    .filter(|n| n.len() == 2)
+                    .ok_or_else(|| error(line_no, value_column, "key expects `x y`".to_string()))?;
+                entities.push(Placement::Key(numbers[0], numbers[1]));
+                entities_at.push((line_no, value_column));
+            }
+            (Some("decoration"), None, _) => {
+                let mut fields = value.splitn(3, char::is_whitespace);
+                let placement = match (fields.next(), fields.next(), fields.next()) {
+                    (Some(x), Some(y), Some(sprite)) if !sprite.trim().is_empty() => {
+                        x.parse::<f32>().ok().zip(y.parse::<f32>().ok()).map(|(x, y)| {
+                            Placement::Decoration(sprite.trim().to_string(), x, y)
+                        })
+                    }
+                    _ => None,
+                };
+                let placement = placement
+                    .ok_or_else(|| error(line_no, value_column, "decoration expects `x y sprite`".to_string()))?;
+                entities.push(placement);
+                entities_at.push((line_no, value_column));
+            }
+            (Some("texture"), Some(id), None) => {
+                let id = match id {
+                    "door" => Some(door::DOOR_CELL),
+                    _ => id.parse::<u8>().ok().filter(|id| (1..=9).contains(id)),
+                }
+                .ok_or_else(|| error(line_no, line.column, format!("invalid wall type '{}'", id)))?;
+                if value.is_empty() {
+                    return Err(error(line_no, value_column, "texture expects a file path".to_string()));
+                }
+                textures.insert(id, value.to_string());
+            }
+            // Repeat the key to build a playlist
+            (Some("music"), None, _) => {
+                if value.is_empty() {
+                    return Err(error(line_no, value_column, "music expects a file path".to_string()));
+                }
+                music.push(value.to_string());
+            }
+            _ => return Err(error(line_no, line.column, format!("unknown key '{}'", key))),
+        }
+    }
+
+    if data.is_empty() {
+        return Err(error(0, 0, "missing [grid] section".to_string()));
+    }
+
+    let Some(spawn) = spawn else {
+        return Err(error(0, 0, "missing spawn".to_string()));
+    };
+    if !is_open_cell(&data, spawn.0, spawn.1) {
+        return Err(error(spawn_at.0, spawn_at.1, "spawn is outside the map or inside a wall".to_string()));
+    }
+    // A door slides into the walls on either side of it
+    let is_wall = |x: i64, y: i64| {
+        x < 0 || y < 0 || data.get(y as usize).and_then(|row| row.get(x as usize)).is_none_or(|cell| *cell > 0)
+    };
+    for (x, y, _, (line_no, column)) in &doors {
+        let (x, y) = (*x as i64, *y as i64);
+        let between_walls = (is_wall(x - 1, y) && is_wall(x + 1, y)) || (is_wall(x, y - 1) && is_wall(x, y + 1));
+        if !between_walls {
+            return Err(error(*line_no, *column, "door needs walls on two opposite sides".to_string()));
+        }
+    }
+    let doors = doors.into_iter().map(|(x, y, kind, _)| (x, y, kind)).collect();
+
+    for (entity, (line_no, column)) in entities.iter().zip(entities_at) {
+        if !entity.points().iter().all(|point| is_open_cell(&data, point.0, point.1)) {
+            return Err(error(line_no, column, format!("{} is outside the map or inside a wall", entity.kind())));
+        }
+    }
+
+    Ok(LevelFile {
+        name,
+        data,
+        spawn,
+        entities,
+        doors,
+        textures,
+        music,
+    })
+}
+
+fn parse_numbers(value: &str) -> Option<Vec<f32>> {
+    value.split_whitespace().map(|n| n.parse::<f32>().ok()).collect()
+}
+
+fn is_open_cell(data: &[Vec<u8>], x: f32, y: f32) -> bool {
+    if x < 0.0 || y < 0.0 {
+        return false;
+    }
+    match data.get(y as usize).and_then(|row| row.get(x as usize)) {
+        Some(cell) => *cell == 0,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<LevelFile, LevelError> {
+        parse_level(Path::new("test.level"), source)
+    }
+
+    #[test]
+    fn parses_a_good_level() {
+        let level = parse(
+            "name = Test\n\
+             spawn = 1.5 1.5 0.5\n\
+             coin = 2.5 1.5\n\
+             texture 1 = assets/textures/dungeon.jpg\n\
+             \n\
+             [grid]\n\
+             1111\n\
+             1001\n\
+             1111\n",
+        )
+        .unwrap();
+
+        assert_eq!(level.name, "Test");
+        assert_eq!(level.spawn, (1.5, 1.5, 0.5));
+        assert_eq!(level.entities, vec![Placement::Coin(2.5, 1.5)]);
+        assert_eq!(level.data, vec![vec![1, 1, 1, 1], vec![1, 0, 0, 1], vec![1, 1, 1, 1]]);
+        assert_eq!(level.textures.get(&1).map(String::as_str), Some("assets/textures/dungeon.jpg"));
+    }
+
+    #[test]
+    fn reports_the_position_of_a_bad_grid_character() {
+        let error = parse("spawn = 1.5 1.5\n[grid]\n1111\n  10x1\n1111\n").err().unwrap();
+
+        assert_eq!((error.line, error.column), (4, 5));
+        assert!(error.message.contains("'x'"), "{}", error.message);
+    }
+
+    #[test]
+    fn reports_the_position_of_a_bad_spawn_line() {
+        let error = parse("name = Test\nspawn =  1.5\n[grid]\n111\n101\n111\n").err().unwrap();
+
+        assert_eq!((error.line, error.column), (2, 10));
+        assert_eq!(error.message, "spawn expects `x y [angle]`");
+    }
+
+    #[test]
+    fn reports_a_spawn_inside_a_wall_at_its_line() {
+        let error = parse("\nspawn = 0.5 0.5\n[grid]\n111\n101\n111\n").err().unwrap();
+
+        assert_eq!((error.line, error.column), (2, 9));
+        assert_eq!(error.message, "spawn is outside the map or inside a wall");
+    }
+
+    #[test]
+    fn reports_a_door_without_walls_on_two_opposite_sides() {
+        let error = parse("spawn = 1.5 1.5\n[grid]\n1111\n10D1\n1001\n1111\n").err().unwrap();
+
+        assert_eq!((error.line, error.column), (4, 3));
+        assert_eq!(error.message, "door needs walls on two opposite sides");
+    }
+
+    #[test]
+    fn the_lists_come_from_the_loaded_levels() {
+        let levels = load_levels(LEVELS_DIR);
+
+        assert!(levels.iter().all(|(_, level)| level.is_ok()));
+        assert_eq!(level_ids(&levels), vec!["level1.level", "level2.level", "level3.level"]);
+        assert_eq!(level_names(&levels), vec!["The Beginning", "The Corridors", "The Maze"]);
+        assert_eq!(texture_paths(&levels), vec!["assets/textures/dungeon.jpg", "assets/textures/iceDungeon.jpg"]);
+    }
+}
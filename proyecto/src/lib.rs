@@ -0,0 +1,27 @@
+// Game simulation (levels, player movement, coins, enemies), the software
+// raycaster and the audio logic. Nothing in here depends on raylib or an audio
+// device, so tools and tests can drive the game without opening a window;
+// main.rs is the raylib front-end and plugs in the rodio audio backend.
+
+pub mod config;
+pub mod input;
+pub mod level;
+pub mod map;
+pub mod door;
+pub mod player;
+pub mod sprite;
+pub mod entity;
+pub mod sprite_sheet;
+pub mod enemy;
+pub mod pathfind;
+pub mod world;
+pub mod state;
+pub mod pause;
+pub mod settings;
+pub mod settings_menu;
+pub mod progress;
+pub mod leaderboard;
+pub mod audio;
+pub mod framebuffer;
+pub mod raycast;
+pub mod headless;
@@ -1,32 +1,41 @@
 mod game;
-mod player;
-mod raycast;
-mod map;
+mod display;
 mod minimap;
-mod sprite;
-mod audio;
+mod rodio_backend;
 mod menu;
+mod controls;
 
 use raylib::prelude::*;
-
-const SCREEN_WIDTH: i32 = 1024;
-const SCREEN_HEIGHT: i32 = 768;
+use proyecto::{headless, level, raycast};
+use proyecto::settings::{self, Settings};
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--headless") {
+        run_headless(&args);
+        return;
+    }
+
+    let settings = Settings::load(settings::SETTINGS_PATH.as_ref());
     let (mut rl, thread) = raylib::init()
-        .size(SCREEN_WIDTH, SCREEN_HEIGHT)
+        .size(settings.width as i32, settings.height as i32)
         .title("Ray Caster Game")
+        .resizable()
         .build();
+    if settings.fullscreen {
+        rl.toggle_fullscreen();
+    }
 
     rl.set_target_fps(60);
-    rl.disable_cursor();
+    // ESC pauses the game, quitting goes through the title screen
+    rl.set_exit_key(None);
 
-    let mut game = game::Game::new(&mut rl, &thread);
+    let mut game = game::Game::new(&mut rl, &thread, settings);
     
     game.load_textures(&mut rl, &thread);
 
-    while !rl.window_should_close() {
-        game.update(&mut rl);
+    while !rl.window_should_close() && !game.should_quit() {
+        game.update(&mut rl, &thread);
         
         let mut d = rl.begin_drawing(&thread);
         d.clear_background(Color::BLACK);
@@ -34,3 +43,23 @@ fn main() {
         game.draw(&mut d);
     }
 }
+
+fn run_headless(args: &[String]) {
+    let options = match headless::HeadlessOptions::from_args(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, headless::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let mut raycaster = raycast::RayCaster::new(options.width, options.height);
+    if options.textures {
+        display::load_textures(&mut raycaster, &level::load_levels(level::LEVELS_DIR));
+    }
+
+    if let Err(e) = headless::render_to_png(&mut raycaster, &options) {
+        eprintln!("Headless render failed: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,98 +1,91 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::level::{self, LevelError, LevelFile, Placement};
+use crate::door::{self, Door};
+
 #[derive(Clone)]
 pub struct Map {
+    pub name: String,
     pub width: usize,
     pub height: usize,
     pub data: Vec<Vec<u8>>,
+    pub spawn: (f32, f32, f32),
+    pub entities: Vec<Placement>,
+    // Open state of every door, changed while the level is played
+    pub doors: Vec<Door>,
+    pub textures: HashMap<u8, String>,
+    pub music: Vec<String>,
 }
 
 impl Map {
     pub fn new(level: usize) -> Self {
-        match level {
-            0 => Self::level_1(),
-            1 => Self::level_2(),
-            2 => Self::level_3(),
-            _ => Self::level_1(),
+        let levels = level::list_levels(level::LEVELS_DIR);
+
+        // Out of range levels fall back to the first one
+        let Some(path) = levels.get(level).or(levels.first()) else {
+            println!("No level files found in {}, using an empty room", level::LEVELS_DIR);
+            return Self::empty_room();
+        };
+
+        match Self::load(path) {
+            Ok(map) => map,
+            Err(e) => {
+                println!("Failed to load level: {}", e);
+                Self::empty_room()
+            }
         }
     }
 
-    fn level_1() -> Self {
-        let data = vec![
-            vec![1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 2, 2, 2, 0, 0, 0, 0, 0, 0, 3, 3, 3, 0, 1],
-            vec![1, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 1],
-            vec![1, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 4, 4, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 4, 4, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 5, 5, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0, 1],
-            vec![1, 0, 5, 5, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
-        ];
-        
-        Self {
-            width: 16,
-            height: 16,
-            data,
-        }
+    pub fn load(path: &Path) -> Result<Self, LevelError> {
+        Ok(Self::from_level(level::load_level(path)?))
     }
 
-    fn level_2() -> Self {
-        let data = vec![
-            vec![2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
-            vec![2, 0, 0, 0, 3, 3, 3, 0, 0, 3, 3, 3, 0, 0, 0, 2],
-            vec![2, 0, 0, 0, 0, 0, 3, 0, 0, 3, 0, 0, 0, 0, 0, 2],
-            vec![2, 0, 0, 0, 0, 0, 3, 0, 0, 3, 0, 0, 0, 0, 0, 2],
-            vec![2, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 2],
-            vec![2, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 2],
-            vec![2, 4, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 4, 2],
-            vec![2, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 2],
-            vec![2, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 2],
-            vec![2, 4, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 4, 2],
-            vec![2, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 2],
-            vec![2, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 2],
-            vec![2, 0, 0, 0, 0, 0, 5, 0, 0, 5, 0, 0, 0, 0, 0, 2],
-            vec![2, 0, 0, 0, 0, 0, 5, 0, 0, 5, 0, 0, 0, 0, 0, 2],
-            vec![2, 0, 0, 0, 5, 5, 5, 0, 0, 5, 5, 5, 0, 0, 0, 2],
-            vec![2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
-        ];
-        
-        Self {
-            width: 16,
-            height: 16,
-            data,
-        }
+    pub fn from_level(level: LevelFile) -> Self {
+        let mut map = Self {
+            name: level.name,
+            width: level.data[0].len(),
+            height: level.data.len(),
+            data: level.data,
+            spawn: level.spawn,
+            entities: level.entities,
+            doors: Vec::new(),
+            textures: level.textures,
+            music: level.music,
+        };
+
+        // Doors are still shut here, so they count as walls for each other
+        map.doors = level
+            .doors
+            .iter()
+            .map(|(x, y, kind)| {
+                let vertical = map.is_wall(*x, y.wrapping_sub(1)) && map.is_wall(*x, y + 1);
+                Door::new(*x, *y, *kind, vertical)
+            })
+            .collect();
+        map
     }
 
-    fn level_3() -> Self {
-        let data = vec![
-            vec![3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3],
-            vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3],
-            vec![3, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1, 0, 3],
-            vec![3, 0, 1, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 1, 0, 3],
-            vec![3, 0, 1, 0, 0, 1, 0, 2, 2, 0, 1, 0, 0, 1, 0, 3],
-            vec![3, 0, 1, 1, 0, 1, 0, 2, 2, 0, 1, 0, 1, 1, 0, 3],
-            vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3],
-            vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3],
-            vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3],
-            vec![3, 0, 1, 1, 0, 1, 0, 2, 2, 0, 1, 0, 1, 1, 0, 3],
-            vec![3, 0, 1, 0, 0, 1, 0, 2, 2, 0, 1, 0, 0, 1, 0, 3],
-            vec![3, 0, 1, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 1, 0, 3],
-            vec![3, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1, 0, 3],
-            vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3],
-            vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3],
-            vec![3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3],
-        ];
-        
+    // Used when no level file could be loaded so the game still starts
+    fn empty_room() -> Self {
+        let size = 8;
+        let data = (0..size)
+            .map(|y| {
+                (0..size)
+                    .map(|x| if x == 0 || y == 0 || x == size - 1 || y == size - 1 { 1 } else { 0 })
+                    .collect()
+            })
+            .collect();
+
         Self {
-            width: 16,
-            height: 16,
+            name: "Empty Room".to_string(),
+            width: size,
+            height: size,
             data,
+            spawn: (1.5, 1.5, 0.0),
+            entities: Vec::new(),
+            doors: Vec::new(),
+            textures: HashMap::new(),
+            music: Vec::new(),
         }
     }
 
@@ -103,39 +96,167 @@ impl Map {
         self.data[y][x]
     }
 
+    // Doors are walls until they are fully open
     pub fn is_wall(&self, x: usize, y: usize) -> bool {
-        self.get_cell(x, y) > 0
+        match self.get_cell(x, y) {
+            0 => false,
+            door::DOOR_CELL => self.door_at(x, y).is_none_or(|door| !door.is_passable()),
+            _ => true,
+        }
+    }
+
+    pub fn door_at(&self, x: usize, y: usize) -> Option<&Door> {
+        self.doors.iter().find(|door| (door.x, door.y) == (x, y))
+    }
+
+    pub fn door_at_mut(&mut self, x: usize, y: usize) -> Option<&mut Door> {
+        self.doors.iter_mut().find(|door| (door.x, door.y) == (x, y))
+    }
+
+    // Cells that can be walked through, for pathfinding
+    pub fn is_walkable(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && !self.is_wall(x as usize, y as usize)
     }
 
     pub fn get_coin_positions(&self) -> Vec<(f32, f32)> {
-        let mut coins = Vec::new();
-        
-        match self.data[0][0] {
-            1 => {
-                coins.push((12.5, 3.5));
-                coins.push((5.5, 10.5));
-                coins.push((12.5, 12.5))
+        self.entities
+            .iter()
+            .filter_map(|entity| match entity {
+                Placement::Coin(x, y) => Some((*x, *y)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // True when no wall cell lies on the straight line between the two points
+    pub fn line_of_sight(&self, from: (f32, f32), to: (f32, f32)) -> bool {
+        let target = (to.0.floor() as i32, to.1.floor() as i32);
+
+        // With the segment as the direction, distance 1 is the end point
+        for step in GridWalk::new(from, (to.0 - from.0, to.1 - from.1)) {
+            if step.distance > 1.0 {
+                break;
             }
-            2 => {
-                coins.push((4.5, 4.5));
-                coins.push((11.5, 4.5));
-                coins.push((7.5, 7.5));
-                coins.push((4.5, 11.5));
-                coins.push((11.5, 11.5));
-                coins.push((7.5, 4.5));
+            let (x, y) = step.cell;
+            if x < 0 || y < 0 || self.is_wall(x as usize, y as usize) {
+                return false;
             }
-            3 => {
-                coins.push((5.5, 5.5));
-                coins.push((10.5, 5.5));
-                coins.push((8.5, 7.5));
-                coins.push((5.5, 10.5));
-                coins.push((10.5, 10.5));
-                coins.push((8.5, 12.5));
-                coins.push((4.5, 8.5));
+            if step.cell == target {
+                break;
             }
-            _ => {}
         }
-        
-        coins
+
+        true
+    }
+}
+
+// A cell entered by a `GridWalk`. Distances are in multiples of the walk's
+// direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridStep {
+    pub cell: (i32, i32),
+    // Where the cell is entered and left
+    pub distance: f32,
+    pub exit: f32,
+    // Entered across a vertical grid line (x changed), else a horizontal one
+    pub is_vertical_wall: bool,
+}
+
+// The cells a ray from `origin` along `dir` passes through, nearest first and
+// without end, found one cell boundary at a time (DDA) so distances and sides
+// are exact. The starting cell isn't included. `dir` needn't be normalized:
+// the raycaster passes rays built from the camera plane, which makes
+// distances perpendicular to the camera.
+pub struct GridWalk {
+    cell: (i32, i32),
+    step: (i32, i32),
+    // Distance to the next vertical / horizontal grid line, and between two
+    side_dist: (f32, f32),
+    delta_dist: (f32, f32),
+}
+
+impl GridWalk {
+    pub fn new(origin: (f32, f32), dir: (f32, f32)) -> Self {
+        let cell = (origin.0.floor() as i32, origin.1.floor() as i32);
+        let delta_dist = (
+            if dir.0 == 0.0 { f32::INFINITY } else { (1.0 / dir.0).abs() },
+            if dir.1 == 0.0 { f32::INFINITY } else { (1.0 / dir.1).abs() },
+        );
+
+        let (step_x, side_x) = if dir.0 < 0.0 {
+            (-1, (origin.0 - cell.0 as f32) * delta_dist.0)
+        } else {
+            (1, (cell.0 as f32 + 1.0 - origin.0) * delta_dist.0)
+        };
+        let (step_y, side_y) = if dir.1 < 0.0 {
+            (-1, (origin.1 - cell.1 as f32) * delta_dist.1)
+        } else {
+            (1, (cell.1 as f32 + 1.0 - origin.1) * delta_dist.1)
+        };
+
+        Self {
+            cell,
+            step: (step_x, step_y),
+            side_dist: (side_x, side_y),
+            delta_dist,
+        }
+    }
+}
+
+impl Iterator for GridWalk {
+    type Item = GridStep;
+
+    fn next(&mut self) -> Option<GridStep> {
+        let is_vertical_wall = self.side_dist.0 < self.side_dist.1;
+        let distance = if is_vertical_wall {
+            self.cell.0 += self.step.0;
+            self.side_dist.0 += self.delta_dist.0;
+            self.side_dist.0 - self.delta_dist.0
+        } else {
+            self.cell.1 += self.step.1;
+            self.side_dist.1 += self.delta_dist.1;
+            self.side_dist.1 - self.delta_dist.1
+        };
+
+        Some(GridStep {
+            cell: self.cell,
+            distance,
+            exit: self.side_dist.0.min(self.side_dist.1),
+            is_vertical_wall,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(number: usize) -> Map {
+        Map::load(Path::new(&format!("assets/levels/level{}.level", number))).unwrap()
+    }
+
+    #[test]
+    fn line_of_sight_stops_at_walls() {
+        let map = level(1);
+        // Along the open row 1, and within one cell
+        assert!(map.line_of_sight((1.5, 1.5), (14.5, 1.5)));
+        assert!(map.line_of_sight((1.2, 1.2), (1.8, 1.7)));
+        // Through the type 2 wall at (2, 3)
+        assert!(!map.line_of_sight((1.5, 3.5), (3.5, 3.5)));
+        // Past the corner of the wall at (2, 2), and into it
+        assert!(map.line_of_sight((1.5, 1.5), (3.5, 1.9)));
+        assert!(!map.line_of_sight((1.5, 1.5), (3.5, 3.1)));
+    }
+
+    #[test]
+    fn line_of_sight_passes_open_doors_only() {
+        let mut map = level(3);
+        // Through the door at (4, 5) into the room above it
+        let (outside, inside) = ((4.5, 7.5), (4.5, 3.5));
+        assert!(!map.line_of_sight(outside, inside));
+        map.door_at_mut(4, 5).unwrap().open_amount = 0.5;
+        assert!(!map.line_of_sight(outside, inside));
+        map.door_at_mut(4, 5).unwrap().open_amount = 1.0;
+        assert!(map.line_of_sight(outside, inside));
     }
 }
@@ -1,50 +1,121 @@
 use raylib::prelude::*;
+use proyecto::input::Control;
+use proyecto::progress::{self, LevelProgress};
+use proyecto::settings::Settings;
+
+// Level list rows start below the title and instructions
+const LEVEL_LIST_TOP: i32 = 285;
+const LEVEL_ROW_HEIGHT: i32 = 36;
+// Space kept at the bottom for the control help, objective and credits
+const HELP_HEIGHT: i32 = 170;
+
+// What the menu did with this frame's input, so the game can react (start a
+// level, play a navigation sound)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuEvent {
+    Moved,
+    Selected(usize),
+}
 
 pub struct Menu {
+    levels: Vec<String>,
+    // Saved progress per entry of `levels`, shown next to the names
+    progress: Vec<Option<LevelProgress>>,
     selected_level: usize,
     title_animation: f32,
 }
 
 impl Menu {
-    pub fn new() -> Self {
+    pub fn new(levels: Vec<String>) -> Self {
         Self {
+            levels,
+            progress: Vec::new(),
             selected_level: 0,
             title_animation: 0.0,
         }
     }
 
-    pub fn update(&mut self, rl: &mut RaylibHandle) -> Option<usize> {
-        self.title_animation += rl.get_frame_time();
+    pub fn set_progress(&mut self, progress: Vec<Option<LevelProgress>>) {
+        self.progress = progress;
+    }
+
+    pub fn selected_level(&self) -> usize {
+        self.selected_level
+    }
+
+    pub fn level_name(&self, level: usize) -> Option<&str> {
+        self.levels.get(level).map(|name| name.as_str())
+    }
+
+    // Keeps the background and title moving on screens without a level list
+    pub fn animate(&mut self, dt: f32) {
+        self.title_animation += dt;
+    }
+
+    pub fn update(&mut self, rl: &mut RaylibHandle) -> Option<MenuEvent> {
+        self.animate(rl.get_frame_time());
         
         // Arrow key navigation
         if rl.is_key_pressed(KeyboardKey::KEY_UP) && self.selected_level > 0 {
             self.selected_level -= 1;
+            return Some(MenuEvent::Moved);
         }
-        if rl.is_key_pressed(KeyboardKey::KEY_DOWN) && self.selected_level < 2 {
+        if rl.is_key_pressed(KeyboardKey::KEY_DOWN) && self.selected_level + 1 < self.levels.len() {
             self.selected_level += 1;
+            return Some(MenuEvent::Moved);
         }
         
         // Enter to start selected level
-        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
-            return Some(self.selected_level);
+        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) && !self.levels.is_empty() {
+            return Some(MenuEvent::Selected(self.selected_level));
         }
 
         // Number keys for quick selection
-        if rl.is_key_pressed(KeyboardKey::KEY_ONE) {
-            return Some(0);
-        }
-        if rl.is_key_pressed(KeyboardKey::KEY_TWO) {
-            return Some(1);
-        }
-        if rl.is_key_pressed(KeyboardKey::KEY_THREE) {
-            return Some(2);
+        let number_keys = [
+            KeyboardKey::KEY_ONE,
+            KeyboardKey::KEY_TWO,
+            KeyboardKey::KEY_THREE,
+            KeyboardKey::KEY_FOUR,
+            KeyboardKey::KEY_FIVE,
+            KeyboardKey::KEY_SIX,
+            KeyboardKey::KEY_SEVEN,
+            KeyboardKey::KEY_EIGHT,
+            KeyboardKey::KEY_NINE,
+        ];
+        for (level, key) in number_keys.iter().enumerate().take(self.levels.len()) {
+            if rl.is_key_pressed(*key) {
+                self.selected_level = level;
+                return Some(MenuEvent::Selected(level));
+            }
         }
 
         None
     }
 
-    pub fn draw(&self, d: &mut RaylibDrawHandle) {
-        // Animated background
+    pub fn draw_title(&self, d: &mut RaylibDrawHandle, settings: &Settings) {
+        self.draw_background(d);
+
+        let prompts = [
+            ("Press ENTER to play".to_string(), Color::YELLOW),
+            ("TAB - Settings".to_string(), Color::LIGHTGRAY),
+            (format!("{} - Quit", settings.key(Control::Pause)), Color::LIGHTGRAY),
+        ];
+        for (i, (prompt, color)) in prompts.iter().enumerate() {
+            let width = measure_text(prompt, 24);
+            d.draw_text(prompt, d.get_screen_width() / 2 - width / 2, 320 + i as i32 * 40, 24, *color);
+        }
+
+        let bottom = d.get_screen_height();
+        d.draw_text("Made with Rust & Raylib", 10, bottom - 28, 16, Color::DARKGRAY);
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, settings: &Settings) {
+        self.draw_background(d);
+        self.draw_level_list(d, settings);
+    }
+
+    // Animated background and title shared by the menu screens
+    pub fn draw_background(&self, d: &mut RaylibDrawHandle) {
         let bg_color = Color::new(
             (20.0 + (self.title_animation * 0.5).sin() * 10.0) as u8,
             (30.0 + (self.title_animation * 0.3).sin() * 15.0) as u8,
@@ -54,6 +125,7 @@ impl Menu {
         d.clear_background(bg_color);
 
         // Animated title
+        let center = d.get_screen_width() / 2;
         let title = "RAY CASTER GAME";
         let title_size = 48;
         let title_width = measure_text(title, title_size);
@@ -62,7 +134,7 @@ impl Menu {
         // Drop shadow
         d.draw_text(
             title,
-            512 - title_width / 2 + 3,
+            center - title_width / 2 + 3,
             title_y as i32 + 3,
             title_size,
             Color::BLACK,
@@ -71,31 +143,49 @@ impl Menu {
         // Main title
         d.draw_text(
             title,
-            512 - title_width / 2,
+            center - title_width / 2,
             title_y as i32,
             title_size,
             Color::WHITE,
         );
+    }
+
+    fn draw_level_list(&self, d: &mut RaylibDrawHandle, settings: &Settings) {
+        // Everything below the title is laid out around the middle of the screen
+        let (width, height) = (d.get_screen_width(), d.get_screen_height());
+        let center = width / 2;
+        let left = center - 62;
 
         // Instructions
-        let instruction = "Use WASD to move, Mouse to look around";
-        let inst_width = measure_text(instruction, 20);
+        let movement = [Control::Forward, Control::StrafeLeft, Control::Back, Control::StrafeRight];
+        let instruction = format!("Use {} to move, Mouse to look around", settings.key_list(&movement));
+        let inst_width = measure_text(&instruction, 20);
         d.draw_text(
-            instruction,
-            512 - inst_width / 2,
-            250,
+            &instruction,
+            center - inst_width / 2,
+            LEVEL_LIST_TOP - 65,
             20,
             Color::LIGHTGRAY,
         );
 
+        // The help sits above the credits; the level list scrolls in what is left
+        let help_y = height - HELP_HEIGHT;
+
         // Level selection
-        let levels = ["Level 1: The Beginning", "Level 2: The Corridors", "Level 3: The Maze"];
-        let start_y = 320;
+        d.draw_text("Select Level:", left, LEVEL_LIST_TOP - 30, 24, Color::WHITE);
 
-        d.draw_text("Select Level:", 450, start_y - 30, 24, Color::WHITE);
+        if self.levels.is_empty() {
+            d.draw_text("No levels found in assets/levels", left, LEVEL_LIST_TOP, 24, Color::RED);
+        }
+
+        let fits = ((help_y - 20 - LEVEL_LIST_TOP) / LEVEL_ROW_HEIGHT).max(1) as usize;
+        let first = self
+            .selected_level
+            .saturating_sub(fits / 2)
+            .min(self.levels.len().saturating_sub(fits));
 
-        for (i, level_name) in levels.iter().enumerate() {
-            let y = start_y + i as i32 * 40;
+        for (i, level_name) in self.levels.iter().enumerate().skip(first).take(fits) {
+            let y = LEVEL_LIST_TOP + (i - first) as i32 * LEVEL_ROW_HEIGHT;
             let color = if i == self.selected_level {
                 Color::YELLOW
             } else {
@@ -104,33 +194,73 @@ impl Menu {
 
             // Selection arrow
             if i == self.selected_level {
-                d.draw_text(">", 420, y, 24, Color::YELLOW);
+                d.draw_text(">", left - 30, y, 24, Color::YELLOW);
             }
 
-            d.draw_text(level_name, 450, y, 24, color);
+            let label = format!("Level {}: {}", i + 1, level_name);
+            d.draw_text(&label, left, y, 24, color);
+
+            if let Some(Some(saved)) = self.progress.get(i) {
+                draw_level_progress(d, left + measure_text(&label, 24) + 15, y, saved);
+            }
         }
 
         // Controls section
-        let controls_y = start_y + 180;
-        d.draw_text("Game Controls:", 450, controls_y, 20, Color::LIGHTGRAY);
-        d.draw_text("↑↓ - Select Level", 450, controls_y + 25, 16, Color::GRAY);
-        d.draw_text("ENTER - Start Game", 450, controls_y + 45, 16, Color::GRAY);
-        d.draw_text("1,2,3 - Quick Select", 450, controls_y + 65, 16, Color::GRAY);
-        d.draw_text("ESC - Return to Menu (in game)", 450, controls_y + 85, 16, Color::GRAY);
-        
-        // Music controls
-        d.draw_text("Music Controls:", 450, controls_y + 110, 16, Color::LIGHTBLUE);
-        d.draw_text("M - Toggle Music On/Off", 450, controls_y + 130, 14, Color::GRAY);
-        d.draw_text("-/+ - Volume Down/Up", 450, controls_y + 150, 14, Color::GRAY);
+        let controls_x = center - 300;
+        d.draw_text("Game Controls:", controls_x, help_y, 20, Color::LIGHTGRAY);
+        d.draw_text("↑↓ - Select Level", controls_x, help_y + 25, 16, Color::GRAY);
+        d.draw_text("ENTER - Start Game", controls_x, help_y + 45, 16, Color::GRAY);
+        d.draw_text("1-9 - Quick Select   L - Best Runs", controls_x, help_y + 65, 16, Color::GRAY);
+        let pause = format!("{} - Back to Title, Pause (in game)", settings.key(Control::Pause));
+        d.draw_text(&pause, controls_x, help_y + 85, 16, Color::GRAY);
+
+        // Music controls, in a second column
+        let music_x = center + 40;
+        d.draw_text("Music Controls:", music_x, help_y + 2, 16, Color::LIGHTBLUE);
+        let music_help = [
+            format!("{} - Toggle Music On/Off", settings.key(Control::ToggleMusic)),
+            format!("{} - Volume Down/Up", settings.key_list(&[Control::VolumeDown, Control::VolumeUp])),
+            format!(
+                "{} - Effects Volume Down/Up",
+                settings.key_list(&[Control::SfxVolumeDown, Control::SfxVolumeUp])
+            ),
+        ];
+        for (i, line) in music_help.iter().enumerate() {
+            d.draw_text(line, music_x, help_y + 25 + i as i32 * 20, 14, Color::GRAY);
+        }
 
         // Objective
-        d.draw_text("Objective: Collect all coins to win!", 350, controls_y + 180, 20, Color::GOLD);
+        let objective = "Objective: Collect all coins to win!";
+        d.draw_text(objective, center - measure_text(objective, 20) / 2, height - 60, 20, Color::GOLD);
 
         // Credits
-        d.draw_text("Made with Rust & Raylib", 10, 740, 16, Color::DARKGRAY);
+        d.draw_text("Made with Rust & Raylib", 10, height - 28, 16, Color::DARKGRAY);
+    }
+}
+
+// A checkmark for completed levels, then the best time and coin count
+fn draw_level_progress(d: &mut RaylibDrawHandle, x: i32, y: i32, saved: &LevelProgress) {
+    let mut text_x = x;
+    if saved.completed {
+        let base = Vector2::new(x as f32 + 6.0, y as f32 + 20.0);
+        d.draw_line_ex(Vector2::new(x as f32, y as f32 + 13.0), base, 3.0, Color::LIME);
+        d.draw_line_ex(base, Vector2::new(x as f32 + 16.0, y as f32 + 4.0), 3.0, Color::LIME);
+        text_x += 26;
+    }
+
+    let mut details = Vec::new();
+    if let Some(time) = saved.best_time {
+        details.push(format!("Best {}", progress::format_time(time)));
+    }
+    if saved.best_coins > 0 {
+        details.push(format!("{} coins", saved.best_coins));
+    }
+    if !details.is_empty() {
+        d.draw_text(&details.join("  "), text_x, y + 5, 16, Color::LIGHTGRAY);
     }
 }
 
-fn measure_text(text: &str, font_size: i32) -> i32 {
+// Rough width of default-font text, enough to centre it
+pub fn measure_text(text: &str, font_size: i32) -> i32 {
     text.len() as i32 * font_size / 2
 }
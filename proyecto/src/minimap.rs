@@ -1,10 +1,13 @@
 use raylib::prelude::*;
-use crate::player::Player;
-use crate::map::Map;
-use crate::sprite::SpriteManager;
+use proyecto::player::Player;
+use proyecto::map::Map;
+use proyecto::door;
+use proyecto::sprite::SpriteManager;
+use proyecto::entity::Behaviour;
 
 const MINIMAP_SIZE: i32 = 150;
-const MINIMAP_X: i32 = 1024 - MINIMAP_SIZE - 10;
+// Distance from the right edge of the screen
+const MINIMAP_MARGIN: i32 = 10;
 const MINIMAP_Y: i32 = 10;
 
 pub struct MiniMap;
@@ -15,19 +18,23 @@ impl MiniMap {
     }
 
     pub fn draw(&self, d: &mut RaylibDrawHandle, player: &Player, map: &Map, sprites: &SpriteManager) {
-        d.draw_rectangle(MINIMAP_X - 2, MINIMAP_Y - 2, MINIMAP_SIZE + 4, MINIMAP_SIZE + 4, Color::WHITE);
-        d.draw_rectangle(MINIMAP_X, MINIMAP_Y, MINIMAP_SIZE, MINIMAP_SIZE, Color::BLACK);
+        let minimap_x = d.get_screen_width() - MINIMAP_SIZE - MINIMAP_MARGIN;
+        d.draw_rectangle(minimap_x - 2, MINIMAP_Y - 2, MINIMAP_SIZE + 4, MINIMAP_SIZE + 4, Color::WHITE);
+        d.draw_rectangle(minimap_x, MINIMAP_Y, MINIMAP_SIZE, MINIMAP_SIZE, Color::BLACK);
 
         let cell_size = MINIMAP_SIZE / map.width.max(map.height) as i32;
 
         for y in 0..map.height {
             for x in 0..map.width {
-                let screen_x = MINIMAP_X + x as i32 * cell_size;
+                let screen_x = minimap_x + x as i32 * cell_size;
                 let screen_y = MINIMAP_Y + y as i32 * cell_size;
 
                 let cell_value = map.data[y][x];
                 if cell_value > 0 {
                     let color = match cell_value {
+                        // Open doors are left as floor
+                        door::DOOR_CELL if !map.is_wall(x, y) => continue,
+                        door::DOOR_CELL => Color::BROWN,
                         1 => Color::RED,
                         2 => Color::GREEN,
                         3 => Color::BLUE,
@@ -40,15 +47,20 @@ impl MiniMap {
             }
         }
 
-        for coin in &sprites.coins {
-            if !coin.collected {
-                let screen_x = MINIMAP_X + (coin.x * cell_size as f32) as i32;
-                let screen_y = MINIMAP_Y + (coin.y * cell_size as f32) as i32;
-                d.draw_circle(screen_x, screen_y, 3.0, Color::GOLD);
-            }
+        for entity in sprites.entities.iter().filter(|entity| entity.active) {
+            let (color, radius) = match entity.behaviour {
+                Behaviour::Coin => (Color::GOLD, 3.0),
+                Behaviour::Enemy => (Color::MAROON, 4.0),
+                Behaviour::HealthPack => (Color::LIME, 3.0),
+                Behaviour::Key => (Color::SKYBLUE, 3.0),
+                Behaviour::Decoration | Behaviour::Effect => continue,
+            };
+            let screen_x = minimap_x + (entity.x * cell_size as f32) as i32;
+            let screen_y = MINIMAP_Y + (entity.y * cell_size as f32) as i32;
+            d.draw_circle(screen_x, screen_y, radius, color);
         }
 
-        let player_screen_x = MINIMAP_X + (player.x * cell_size as f32) as i32;
+        let player_screen_x = minimap_x + (player.x * cell_size as f32) as i32;
         let player_screen_y = MINIMAP_Y + (player.y * cell_size as f32) as i32;
         
         d.draw_circle(player_screen_x, player_screen_y, 4.0, Color::WHITE);
@@ -58,6 +70,6 @@ impl MiniMap {
         let end_y = player_screen_y + (player.angle.sin() * dir_length) as i32;
         d.draw_line(player_screen_x, player_screen_y, end_x, end_y, Color::WHITE);
         
-        d.draw_text("MAP", MINIMAP_X, MINIMAP_Y + MINIMAP_SIZE + 5, 16, Color::WHITE);
+        d.draw_text("MAP", minimap_x, MINIMAP_Y + MINIMAP_SIZE + 5, 16, Color::WHITE);
     }
 }
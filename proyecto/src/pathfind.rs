@@ -0,0 +1,173 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use crate::map::Map;
+
+pub type Cell = (i32, i32);
+
+const STRAIGHT_COST: f32 = 1.0;
+const DIAGONAL_COST: f32 = std::f32::consts::SQRT_2;
+
+// Open set entry, ordered so the `BinaryHeap` pops the lowest estimate first
+struct Node {
+    estimate: f32,
+    cell: Cell,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+// The cell a world position is in
+pub fn cell_at(x: f32, y: f32) -> Cell {
+    (x.floor() as i32, y.floor() as i32)
+}
+
+// Centre of a cell in world coordinates
+pub fn cell_center(cell: Cell) -> (f32, f32) {
+    (cell.0 as f32 + 0.5, cell.1 as f32 + 0.5)
+}
+
+// A* over the walkable cells of `map`, moving in 8 directions. Diagonal steps
+// need both side cells open so paths don't cut wall corners. Returns the
+// cells after `from` up to and including `to`, or `None` when `to` can't be
+// reached.
+pub fn find_path(map: &Map, from: Cell, to: Cell) -> Option<Vec<Cell>> {
+    if !map.is_walkable(from.0, from.1) || !map.is_walkable(to.0, to.1) {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Cell, Cell> = HashMap::new();
+    let mut cost: HashMap<Cell, f32> = HashMap::new();
+
+    cost.insert(from, 0.0);
+    open.push(Node { estimate: heuristic(from, to), cell: from });
+
+    while let Some(Node { cell, .. }) = open.pop() {
+        if cell == to {
+            let mut path = Vec::new();
+            let mut current = cell;
+            while current != from {
+                path.push(current);
+                current = came_from[&current];
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let cell_cost = cost[&cell];
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            let next = (cell.0 + dx, cell.1 + dy);
+            if !map.is_walkable(next.0, next.1) {
+                continue;
+            }
+            let diagonal = dx != 0 && dy != 0;
+            if diagonal && !(map.is_walkable(cell.0 + dx, cell.1) && map.is_walkable(cell.0, cell.1 + dy)) {
+                continue;
+            }
+
+            let next_cost = cell_cost + if diagonal { DIAGONAL_COST } else { STRAIGHT_COST };
+            if cost.get(&next).is_none_or(|known| next_cost < *known) {
+                cost.insert(next, next_cost);
+                came_from.insert(next, cell);
+                open.push(Node { estimate: next_cost + heuristic(next, to), cell: next });
+            }
+        }
+    }
+
+    None
+}
+
+// Octile distance, exact on an open grid with the step costs above
+fn heuristic(a: Cell, b: Cell) -> f32 {
+    let dx = (a.0 - b.0).abs() as f32;
+    let dy = (a.1 - b.1).abs() as f32;
+    dx.max(dy) * STRAIGHT_COST + dx.min(dy) * (DIAGONAL_COST - STRAIGHT_COST)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level;
+    use std::path::Path;
+
+    fn map(grid: &str) -> Map {
+        let source = format!("spawn = 1.5 1.5\n[grid]\n{}", grid);
+        Map::from_level(level::parse_level(Path::new("test.level"), &source).unwrap())
+    }
+
+    #[test]
+    fn open_cells_are_walked_straight() {
+        let map = map("11111\n\
+                       10001\n\
+                       11111\n");
+
+        assert_eq!(find_path(&map, (1, 1), (3, 1)), Some(vec![(2, 1), (3, 1)]));
+    }
+
+    #[test]
+    fn paths_go_around_a_pillar() {
+        let map = map("11111\n\
+                       10001\n\
+                       10101\n\
+                       10001\n\
+                       11111\n");
+
+        let path = find_path(&map, (1, 2), (3, 2)).unwrap();
+
+        assert_eq!(path.len(), 4);
+        assert_eq!(path.last(), Some(&(3, 2)));
+        assert!(!path.contains(&(2, 2)));
+    }
+
+    #[test]
+    fn diagonal_steps_dont_squeeze_between_wall_corners() {
+        let map = map("11111\n\
+                       10001\n\
+                       10101\n\
+                       11001\n\
+                       11111\n");
+
+        assert_eq!(
+            find_path(&map, (1, 2), (2, 3)),
+            Some(vec![(1, 1), (2, 1), (3, 1), (3, 2), (3, 3), (2, 3)])
+        );
+    }
+
+    #[test]
+    fn walled_off_cells_have_no_path() {
+        let map = map("11111\n\
+                       10101\n\
+                       11111\n");
+
+        assert_eq!(find_path(&map, (1, 1), (3, 1)), None);
+        assert_eq!(find_path(&map, (1, 1), (2, 1)), None);
+    }
+
+    #[test]
+    fn closed_doors_block_paths_until_they_open() {
+        let mut map = map("11111\n\
+                           10D01\n\
+                           11111\n");
+        assert_eq!(find_path(&map, (1, 1), (3, 1)), None);
+
+        map.door_at_mut(2, 1).unwrap().open_amount = 1.0;
+        assert_eq!(find_path(&map, (1, 1), (3, 1)), Some(vec![(2, 1), (3, 1)]));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::input::{Action, PlayerCommand};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseItem {
+    Resume,
+    Restart,
+    Settings,
+    Quit,
+}
+
+impl PauseItem {
+    pub fn label(self) -> &'static str {
+        match self {
+            PauseItem::Resume => "Resume",
+            PauseItem::Restart => "Restart Level",
+            PauseItem::Settings => "Settings",
+            PauseItem::Quit => "Quit to Menu",
+        }
+    }
+}
+
+pub const PAUSE_ITEMS: [PauseItem; 4] = [
+    PauseItem::Resume,
+    PauseItem::Restart,
+    PauseItem::Settings,
+    PauseItem::Quit,
+];
+
+// The list on the pause screen. Up/down move the highlight, confirm picks it
+#[derive(Default)]
+pub struct PauseMenu {
+    selected: usize,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self { selected: 0 }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    // Highlights an item directly, for the mouse
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(PAUSE_ITEMS.len() - 1);
+    }
+
+    // Back to "Resume", for when the game is paused again
+    pub fn reset(&mut self) {
+        self.selected = 0;
+    }
+
+    // The item picked this frame, if any
+    pub fn update(&mut self, cmd: &PlayerCommand) -> Option<PauseItem> {
+        if cmd.has(Action::MenuUp) && self.selected > 0 {
+            self.selected -= 1;
+        }
+        if cmd.has(Action::MenuDown) && self.selected + 1 < PAUSE_ITEMS.len() {
+            self.selected += 1;
+        }
+
+        if cmd.has(Action::Confirm) {
+            Some(PAUSE_ITEMS[self.selected])
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pressed(actions: &[Action]) -> PlayerCommand {
+        PlayerCommand {
+            actions: actions.to_vec(),
+            ..PlayerCommand::default()
+        }
+    }
+
+    #[test]
+    fn up_and_down_move_the_highlight() {
+        let mut menu = PauseMenu::new();
+        assert_eq!(menu.update(&pressed(&[Action::MenuDown])), None);
+        assert_eq!(menu.update(&pressed(&[Action::MenuDown])), None);
+        assert_eq!(menu.selected(), 2);
+        assert_eq!(menu.update(&pressed(&[Action::MenuUp])), None);
+        assert_eq!(menu.selected(), 1);
+    }
+
+    #[test]
+    fn the_highlight_stops_at_both_ends() {
+        let mut menu = PauseMenu::new();
+        menu.update(&pressed(&[Action::MenuUp]));
+        assert_eq!(menu.selected(), 0);
+
+        for _ in 0..10 {
+            menu.update(&pressed(&[Action::MenuDown]));
+        }
+        assert_eq!(menu.selected(), PAUSE_ITEMS.len() - 1);
+
+        menu.select(99);
+        assert_eq!(menu.selected(), PAUSE_ITEMS.len() - 1);
+        menu.reset();
+        assert_eq!(menu.selected(), 0);
+    }
+
+    #[test]
+    fn confirm_picks_the_highlighted_item() {
+        let mut menu = PauseMenu::new();
+        assert_eq!(menu.update(&pressed(&[Action::Confirm])), Some(PauseItem::Resume));
+
+        for item in [PauseItem::Restart, PauseItem::Settings, PauseItem::Quit] {
+            assert_eq!(menu.update(&pressed(&[Action::MenuDown, Action::Confirm])), Some(item));
+        }
+
+        menu.select(1);
+        assert_eq!(menu.update(&pressed(&[Action::Confirm])), Some(PauseItem::Restart));
+    }
+}
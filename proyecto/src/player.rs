@@ -1,12 +1,30 @@
-use raylib::prelude::*;
+use crate::input::PlayerCommand;
 use crate::map::Map;
 use std::f32::consts::PI;
 
+pub const DEFAULT_RADIUS: f32 = 0.2;
+// Radians of turn per pixel of mouse movement
+pub const MOUSE_SENSITIVITY: f32 = 0.002;
+pub const MAX_HEALTH: i32 = 100;
+// Seconds after a hit during which further hits are ignored
+const INVULNERABLE_SECONDS: f32 = 0.8;
+
+// Pushing out of one wall can push into another near corners, so overlaps are
+// resolved a few times per step
+const COLLISION_PASSES: usize = 3;
+
 pub struct Player {
     pub x: f32,
     pub y: f32,
     pub angle: f32,
     pub fov: f32,
+    // The player is a circle of this radius for collision, in cells
+    pub radius: f32,
+    // Negative to invert mouse look
+    pub mouse_sensitivity: f32,
+    pub health: i32,
+    // Seconds of invulnerability left from the last hit
+    pub invulnerable: f32,
     move_speed: f32,
     rot_speed: f32,
 }
@@ -18,59 +36,42 @@ impl Player {
             y,
             angle,
             fov: PI / 3.0,
+            radius: DEFAULT_RADIUS,
+            mouse_sensitivity: MOUSE_SENSITIVITY,
+            health: MAX_HEALTH,
+            invulnerable: 0.0,
             move_speed: 3.0,
             rot_speed: 2.0,
         }
     }
 
-    pub fn update(&mut self, rl: &mut RaylibHandle, map: &Map) {
-        let dt = rl.get_frame_time();
-        
-        let mouse_delta = rl.get_mouse_delta();
-        self.angle += mouse_delta.x * 0.002;
+    pub fn update(&mut self, cmd: &PlayerCommand, dt: f32, map: &Map) {
+        self.invulnerable = (self.invulnerable - dt).max(0.0);
+        self.angle += cmd.mouse_dx * self.mouse_sensitivity;
         
-        let mut move_x = 0.0;
-        let mut move_y = 0.0;
-
-        if rl.is_key_down(KeyboardKey::KEY_W) {
-            move_x += self.angle.cos();
-            move_y += self.angle.sin();
-        }
-        if rl.is_key_down(KeyboardKey::KEY_S) {
-            move_x -= self.angle.cos();
-            move_y -= self.angle.sin();
-        }
-        if rl.is_key_down(KeyboardKey::KEY_A) {
-            move_x += (self.angle - PI / 2.0).cos();
-            move_y += (self.angle - PI / 2.0).sin();
-        }
-        if rl.is_key_down(KeyboardKey::KEY_D) {
-            move_x += (self.angle + PI / 2.0).cos();
-            move_y += (self.angle + PI / 2.0).sin();
-        }
+        let mut move_x = self.angle.cos() * cmd.forward + (self.angle + PI / 2.0).cos() * cmd.strafe;
+        let mut move_y = self.angle.sin() * cmd.forward + (self.angle + PI / 2.0).sin() * cmd.strafe;
 
-        if rl.is_key_down(KeyboardKey::KEY_LEFT) {
-            self.angle -= self.rot_speed * dt;
-        }
-        if rl.is_key_down(KeyboardKey::KEY_RIGHT) {
-            self.angle += self.rot_speed * dt;
-        }
+        self.angle += self.rot_speed * cmd.turn * dt;
 
+        // Full diagonal input isn't faster than straight input, partial analog
+        // input keeps its partial speed
         let move_len = (move_x * move_x + move_y * move_y).sqrt();
-        if move_len > 0.0 {
+        if move_len > 1.0 {
             move_x /= move_len;
             move_y /= move_len;
         }
 
-        let new_x = self.x + move_x * self.move_speed * dt;
-        let new_y = self.y + move_y * self.move_speed * dt;
+        // Long frames are split into steps shorter than the radius so the
+        // player can't tunnel through a wall corner
+        let distance = self.move_speed * dt;
+        let steps = (distance / (self.radius * 0.5)).ceil().max(1.0) as usize;
+        let step = distance / steps as f32;
 
-        if !map.is_wall(new_x as usize, self.y as usize) {
-            self.x = new_x;
-        }
-
-        if !map.is_wall(self.x as usize, new_y as usize) {
-            self.y = new_y;
+        for _ in 0..steps {
+            self.x += move_x * step;
+            self.y += move_y * step;
+            self.resolve_collisions(map);
         }
 
         while self.angle < 0.0 {
@@ -81,7 +82,281 @@ impl Player {
         }
     }
 
+    // Pushes the player's circle out of every wall cell it overlaps. The push
+    // is along the line from the closest point of the cell to the centre, so
+    // moving into a wall at an angle slides along it and corners are rounded.
+    fn resolve_collisions(&mut self, map: &Map) {
+        for _ in 0..COLLISION_PASSES {
+            let mut collided = false;
+
+            let min_x = (self.x - self.radius).floor() as i32;
+            let max_x = (self.x + self.radius).floor() as i32;
+            let min_y = (self.y - self.radius).floor() as i32;
+            let max_y = (self.y + self.radius).floor() as i32;
+
+            for cell_y in min_y..=max_y {
+                for cell_x in min_x..=max_x {
+                    if is_solid(map, cell_x, cell_y) && self.push_out_of_cell(cell_x as f32, cell_y as f32) {
+                        collided = true;
+                    }
+                }
+            }
+
+            if !collided {
+                break;
+            }
+        }
+    }
+
+    // Returns whether the circle overlapped the cell at (cell_x, cell_y)
+    fn push_out_of_cell(&mut self, cell_x: f32, cell_y: f32) -> bool {
+        let closest_x = self.x.clamp(cell_x, cell_x + 1.0);
+        let closest_y = self.y.clamp(cell_y, cell_y + 1.0);
+        let dx = self.x - closest_x;
+        let dy = self.y - closest_y;
+        let distance_sq = dx * dx + dy * dy;
+
+        if distance_sq >= self.radius * self.radius {
+            return false;
+        }
+
+        if distance_sq > 0.0 {
+            let distance = distance_sq.sqrt();
+            let push = self.radius - distance;
+            self.x += dx / distance * push;
+            self.y += dy / distance * push;
+        } else {
+            // Centre inside the cell: leave through the nearest face
+            let exits = [
+                (self.x - cell_x + self.radius, -1.0, 0.0),
+                (cell_x + 1.0 - self.x + self.radius, 1.0, 0.0),
+                (self.y - cell_y + self.radius, 0.0, -1.0),
+                (cell_y + 1.0 - self.y + self.radius, 0.0, 1.0),
+            ];
+            let (push, dir_x, dir_y) = exits
+                .into_iter()
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .unwrap();
+            self.x += dir_x * push;
+            self.y += dir_y * push;
+        }
+
+        true
+    }
+
+    // Returns whether the hit landed; hits during the invulnerability after
+    // the previous one are ignored
+    pub fn take_damage(&mut self, amount: i32) -> bool {
+        if self.invulnerable > 0.0 || !self.is_alive() {
+            return false;
+        }
+        self.health = (self.health - amount).max(0);
+        self.invulnerable = INVULNERABLE_SECONDS;
+        true
+    }
+
+    pub fn heal(&mut self, amount: i32) {
+        self.health = (self.health + amount).min(MAX_HEALTH);
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.health > 0
+    }
+
     pub fn get_direction(&self) -> (f32, f32) {
         (self.angle.cos(), self.angle.sin())
     }
+
+    // Perpendicular to the view direction, pointing to the right of the screen,
+    // scaled so the edges of the screen sit at +-fov/2
+    pub fn get_camera_plane(&self) -> (f32, f32) {
+        let half_width = (self.fov / 2.0).tan();
+        (-self.angle.sin() * half_width, self.angle.cos() * half_width)
+    }
+}
+
+// Cells outside the map count as walls
+fn is_solid(map: &Map, x: i32, y: i32) -> bool {
+    x < 0 || y < 0 || map.is_wall(x as usize, y as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+
+    const DT: f32 = 0.1;
+
+    // Level 1 is open around (7.5, 8.5), more than a step from any wall
+    fn open_level() -> Map {
+        Map::load(Path::new("assets/levels/level1.level")).unwrap()
+    }
+
+    fn level(number: usize) -> Map {
+        Map::load(Path::new(&format!("assets/levels/level{}.level", number))).unwrap()
+    }
+
+    // Distance from the player's centre to the closest wall cell around it
+    fn clearance(player: &Player, map: &Map) -> f32 {
+        let (cell_x, cell_y) = (player.x.floor() as i32, player.y.floor() as i32);
+        let mut closest = f32::INFINITY;
+        for y in cell_y - 2..=cell_y + 2 {
+            for x in cell_x - 2..=cell_x + 2 {
+                if is_solid(map, x, y) {
+                    let dx = player.x - player.x.clamp(x as f32, x as f32 + 1.0);
+                    let dy = player.y - player.y.clamp(y as f32, y as f32 + 1.0);
+                    closest = closest.min((dx * dx + dy * dy).sqrt());
+                }
+            }
+        }
+        closest
+    }
+
+    fn assert_pose(player: &Player, x: f32, y: f32, angle: f32) {
+        let pose = (player.x, player.y, player.angle);
+        assert!(
+            (pose.0 - x).abs() < 1e-5 && (pose.1 - y).abs() < 1e-5 && (pose.2 - angle).abs() < 1e-5,
+            "pose {:?} != {:?}",
+            pose,
+            (x, y, angle)
+        );
+    }
+
+    #[test]
+    fn forward_moves_along_the_view_direction() {
+        let map = open_level();
+        let mut player = Player::new(7.5, 8.5, 0.0);
+        player.update(&PlayerCommand::new(1.0, 0.0, 0.0, 0.0), DT, &map);
+        assert_pose(&player, 7.8, 8.5, 0.0);
+
+        let mut player = Player::new(7.5, 8.5, PI / 2.0);
+        player.update(&PlayerCommand::new(-1.0, 0.0, 0.0, 0.0), DT, &map);
+        assert_pose(&player, 7.5, 8.2, PI / 2.0);
+    }
+
+    #[test]
+    fn strafe_moves_to_the_right_of_the_view() {
+        let map = open_level();
+        // Facing east, the right of the screen is +y
+        let mut player = Player::new(7.5, 8.5, 0.0);
+        player.update(&PlayerCommand::new(0.0, 1.0, 0.0, 0.0), DT, &map);
+        assert_pose(&player, 7.5, 8.8, 0.0);
+
+        // Diagonal input isn't faster than straight input
+        let mut player = Player::new(7.5, 8.5, 0.0);
+        player.update(&PlayerCommand::new(1.0, -1.0, 0.0, 0.0), DT, &map);
+        let side = 0.3 / 2.0_f32.sqrt();
+        assert_pose(&player, 7.5 + side, 8.5 - side, 0.0);
+    }
+
+    #[test]
+    fn partial_input_moves_at_partial_speed() {
+        let map = open_level();
+        let mut player = Player::new(7.5, 8.5, 0.0);
+        player.update(&PlayerCommand::new(0.5, 0.0, 0.0, 0.0), DT, &map);
+        assert_pose(&player, 7.65, 8.5, 0.0);
+
+        let mut player = Player::new(7.5, 8.5, 0.0);
+        player.update(&PlayerCommand::new(0.3, 0.4, 0.0, 0.0), DT, &map);
+        assert_pose(&player, 7.59, 8.62, 0.0);
+    }
+
+    #[test]
+    fn turning_changes_only_the_angle() {
+        let map = open_level();
+        let mut player = Player::new(7.5, 8.5, 0.0);
+        player.update(&PlayerCommand::new(0.0, 0.0, 1.0, 0.0), 0.5, &map);
+        assert_pose(&player, 7.5, 8.5, 1.0);
+
+        // Wraps into 0..2pi
+        let mut player = Player::new(7.5, 8.5, 0.0);
+        player.update(&PlayerCommand::new(0.0, 0.0, -1.0, 0.0), 0.5, &map);
+        assert_pose(&player, 7.5, 8.5, 2.0 * PI - 1.0);
+
+        let mut player = Player::new(7.5, 8.5, 0.0);
+        player.update(&PlayerCommand::new(0.0, 0.0, 0.0, 100.0), DT, &map);
+        assert_pose(&player, 7.5, 8.5, 100.0 * MOUSE_SENSITIVITY);
+    }
+
+    #[test]
+    fn the_same_commands_give_the_same_path() {
+        let map = open_level();
+        let commands = [
+            PlayerCommand::new(1.0, 0.0, 0.3, 0.0),
+            PlayerCommand::new(1.0, 0.5, 0.0, -12.0),
+            PlayerCommand::new(0.0, -1.0, -1.0, 4.0),
+        ];
+        let replay = || {
+            let mut player = Player::new(1.5, 1.5, 0.0);
+            for i in 0..300 {
+                player.update(&commands[i % commands.len()], 1.0 / 60.0, &map);
+            }
+            (player.x, player.y, player.angle)
+        };
+        assert_eq!(replay(), replay());
+    }
+
+    #[test]
+    fn pushing_into_a_wall_at_45_degrees_slides_along_it() {
+        let map = level(1);
+        // Heading south-west into the outer wall at x = 1
+        let mut player = Player::new(1.5, 7.5, 3.0 * PI / 4.0);
+        let forward = PlayerCommand::new(1.0, 0.0, 0.0, 0.0);
+        for _ in 0..60 {
+            player.update(&forward, 1.0 / 60.0, &map);
+        }
+
+        assert!((player.x - (1.0 + DEFAULT_RADIUS)).abs() < 1e-4, "x = {}", player.x);
+        // Half a second against the wall still covers most of the y component
+        // of a second's walk
+        assert!(player.y > 7.5 + 3.0 * (PI / 4.0).sin() * 0.8, "y = {}", player.y);
+    }
+
+    #[test]
+    fn convex_corners_cannot_be_clipped() {
+        let map = level(1);
+        // The type 4 block covers (7, 5) to (8, 6). Aim at its top-left corner
+        // from either side of the diagonal.
+        for angle in [PI / 4.0 - 0.05, PI / 4.0, PI / 4.0 + 0.05] {
+            let mut player = Player::new(6.0, 4.0, angle);
+            let forward = PlayerCommand::new(1.0, 0.0, 0.0, 0.0);
+            for _ in 0..90 {
+                player.update(&forward, 1.0 / 60.0, &map);
+                let dx = player.x - player.x.clamp(7.0, 9.0);
+                let dy = player.y - player.y.clamp(5.0, 7.0);
+                assert!((dx * dx + dy * dy).sqrt() >= DEFAULT_RADIUS - 1e-4, "inside the block at angle {}", angle);
+            }
+        }
+    }
+
+    #[test]
+    fn a_long_frame_does_not_tunnel_through_a_wall() {
+        let map = level(1);
+        // The type 2 wall at (2, 3) is one cell thick; a 2 second frame would
+        // carry the player 6 cells
+        let mut player = Player::new(1.5, 3.5, 0.0);
+        player.update(&PlayerCommand::new(1.0, 0.0, 0.0, 0.0), 2.0, &map);
+        assert!((player.x - (2.0 - DEFAULT_RADIUS)).abs() < 1e-4, "x = {}", player.x);
+    }
+
+    #[test]
+    fn the_centre_keeps_a_radius_from_every_wall() {
+        // Wander every built-in level, turning and strafing into walls
+        let commands = [
+            PlayerCommand::new(1.0, 0.0, 0.2, 0.0),
+            PlayerCommand::new(1.0, 1.0, 0.0, 30.0),
+            PlayerCommand::new(-1.0, 0.5, -0.7, 0.0),
+            PlayerCommand::new(0.5, -1.0, 0.0, -45.0),
+        ];
+        for number in 1..=3 {
+            let map = level(number);
+            let (x, y, angle) = map.spawn;
+            let mut player = Player::new(x, y, angle);
+            for i in 0..4000 {
+                player.update(&commands[(i / 90) % commands.len()], 1.0 / 30.0, &map);
+                let clearance = clearance(&player, &map);
+                assert!(clearance >= DEFAULT_RADIUS - 1e-4, "level {} step {}: clearance {}", number, i, clearance);
+            }
+        }
+    }
 }
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::config::{self, Entry};
+
+pub const PROGRESS_PATH: &str = "progress.sav";
+// Bumped when the format changes in a way older games can't read
+pub const SAVE_VERSION: u32 = 1;
+
+// What the player has achieved on one level
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LevelProgress {
+    pub completed: bool,
+    // Seconds, of the fastest completion
+    pub best_time: Option<f32>,
+    // Most coins collected in one attempt, finished or not
+    pub best_coins: usize,
+}
+
+// Per-level progress, keyed by level file name so that adding or reordering
+// levels doesn't move records between them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    levels: HashMap<String, LevelProgress>,
+}
+
+impl Progress {
+    // Like the settings, a missing or damaged file never keeps the game from
+    // starting: missing means no progress yet, bad lines are reported and
+    // skipped
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(source) => Self::parse(path, &source),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /*
+     * A version line, then one section per level file:
+     *
+     *   version = 1
+     *
+     *   [level1.level]
+     *   completed = true
+     *   best_time = 42.37
+     *   best_coins = 3
+     */
+    pub fn parse(path: &Path, source: &str) -> Self {
+        let mut progress = Self::default();
+        let mut section: Option<String> = None;
+        let path = path.display();
+
+        for line in config::lines(source) {
+            let (key, value) = match line.entry {
+                Entry::Section(name) => {
+                    progress.levels.entry(name.to_string()).or_default();
+                    section = Some(name.to_string());
+                    continue;
+                }
+                Entry::Pair { key, value, .. } => (key, value),
+                Entry::Text => {
+                    println!("{}:{}: expected `key = value`", path, line.number);
+                    continue;
+                }
+            };
+
+            let Some(level) = section.as_ref().and_then(|name| progress.levels.get_mut(name)) else {
+                if key != "version" {
+                    println!("{}:{}: '{}' outside of a level section", path, line.number, key);
+                    continue;
+                }
+                match value.parse::<u32>() {
+                    Ok(version) if version <= SAVE_VERSION => {}
+                    Ok(version) => {
+                        println!("{}: save version {} is newer than this game ({}), ignoring it", path, version, SAVE_VERSION);
+                        return Self::default();
+                    }
+                    Err(_) => println!("{}:{}: invalid version '{}'", path, line.number, value),
+                }
+                continue;
+            };
+
+            let parsed = match key {
+                "completed" => value.parse::<bool>().ok().map(|v| level.completed = v),
+                "best_time" => value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|time| *time > 0.0)
+                    .map(|time| level.best_time = Some(time)),
+                "best_coins" => value.parse::<usize>().ok().map(|v| level.best_coins = v),
+                _ => {
+                    println!("{}:{}: unknown key '{}'", path, line.number, key);
+                    continue;
+                }
+            };
+            if parsed.is_none() {
+                println!("{}:{}: invalid value '{}' for {}", path, line.number, value, key);
+            }
+        }
+
+        progress
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut names: Vec<&String> = self.levels.keys().collect();
+        names.sort();
+
+        let mut text = format!("version = {}\n", SAVE_VERSION);
+        for name in names {
+            let level = &self.levels[name];
+            text.push_str(&format!("\n[{}]\ncompleted = {}\n", name, level.completed));
+            if let Some(time) = level.best_time {
+                text.push_str(&format!("best_time = {:.2}\n", time));
+            }
+            text.push_str(&format!("best_coins = {}\n", level.best_coins));
+        }
+        text
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn level(&self, name: &str) -> Option<&LevelProgress> {
+        self.levels.get(name)
+    }
+
+    // Records a finished run, keeping the best time and coin count. Returns
+    // whether anything improved, so the caller knows to save.
+    pub fn record_completion(&mut self, name: &str, time: f32, coins: usize) -> bool {
+        let level = self.levels.entry(name.to_string()).or_default();
+        let before = level.clone();
+
+        level.completed = true;
+        if level.best_time.is_none_or(|best| time < best) {
+            level.best_time = Some(time);
+        }
+        level.best_coins = level.best_coins.max(coins);
+
+        *level != before
+    }
+
+    // Records the coins of an attempt that was abandoned
+    pub fn record_attempt(&mut self, name: &str, coins: usize) -> bool {
+        if coins == 0 {
+            return false;
+        }
+
+        let level = self.levels.entry(name.to_string()).or_default();
+        if coins > level.best_coins {
+            level.best_coins = coins;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// Minutes, seconds and hundredths, as shown in the menu
+pub fn format_time(seconds: f32) -> String {
+    let hundredths = (seconds * 100.0).round() as u32;
+    format!("{}:{:02}.{:02}", hundredths / 6000, hundredths / 100 % 60, hundredths % 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Progress {
+        Progress::parse(Path::new(PROGRESS_PATH), source)
+    }
+
+    #[test]
+    fn progress_survives_a_round_trip() {
+        let mut progress = Progress::default();
+        progress.record_completion("level1.level", 42.37, 3);
+        progress.record_attempt("level2.level", 2);
+
+        let loaded = parse(&progress.to_text());
+        assert_eq!(loaded, progress);
+        assert_eq!(
+            loaded.level("level1.level"),
+            Some(&LevelProgress {
+                completed: true,
+                best_time: Some(42.37),
+                best_coins: 3,
+            })
+        );
+        assert_eq!(loaded.level("level2.level").map(|level| level.completed), Some(false));
+    }
+
+    #[test]
+    fn a_newer_save_is_ignored() {
+        let source = format!("version = {}\n\n[level1.level]\ncompleted = true\n", SAVE_VERSION + 1);
+        assert_eq!(parse(&source), Progress::default());
+    }
+
+    #[test]
+    fn an_older_or_damaged_save_keeps_what_it_can() {
+        let progress = parse(
+            "version = 0\nstray = 1\n[level1.level]\ncompleted = yes\nbest_time = -3\nbest_coins = 2\nwho = me\n",
+        );
+        assert_eq!(
+            progress.level("level1.level"),
+            Some(&LevelProgress {
+                completed: false,
+                best_time: None,
+                best_coins: 2,
+            })
+        );
+
+        let progress = parse("version = new\n[level1.level]\ncompleted = true\n");
+        assert_eq!(progress.level("level1.level").map(|level| level.completed), Some(true));
+    }
+
+    #[test]
+    fn the_best_time_only_improves() {
+        let mut progress = Progress::default();
+        assert!(progress.record_completion("level1.level", 30.0, 2));
+        assert!(!progress.record_completion("level1.level", 35.0, 1));
+        assert_eq!(progress.level("level1.level").unwrap().best_time, Some(30.0));
+
+        assert!(progress.record_completion("level1.level", 25.0, 1));
+        let level = progress.level("level1.level").unwrap();
+        assert_eq!(level.best_time, Some(25.0));
+        assert_eq!(level.best_coins, 2);
+    }
+
+    #[test]
+    fn abandoned_attempts_only_raise_the_coin_count() {
+        let mut progress = Progress::default();
+        assert!(!progress.record_attempt("level1.level", 0));
+        assert_eq!(progress.level("level1.level"), None);
+        assert!(progress.record_attempt("level1.level", 2));
+        assert!(!progress.record_attempt("level1.level", 1));
+
+        let level = progress.level("level1.level").unwrap();
+        assert_eq!((level.completed, level.best_time, level.best_coins), (false, None, 2));
+    }
+
+    #[test]
+    fn times_show_minutes_seconds_and_hundredths() {
+        assert_eq!(format_time(0.0), "0:00.00");
+        assert_eq!(format_time(42.375), "0:42.38");
+        assert_eq!(format_time(125.5), "2:05.50");
+    }
+}
@@ -1,439 +1,560 @@
-use raylib::prelude::*;
+use std::collections::HashMap;
 use crate::player::Player;
-use crate::map::Map;
+use crate::map::{GridStep, GridWalk, Map};
+use crate::door::{self, Door};
 use crate::sprite::SpriteManager;
+use crate::entity::Entity;
+use crate::sprite_sheet::SpriteSheet;
+use crate::framebuffer::{FrameBuffer, TextureData, rgba, shade};
 
-const SCREEN_WIDTH: i32 = 1024;
-const SCREEN_HEIGHT: i32 = 768;
-const RAY_COUNT: usize = SCREEN_WIDTH as usize;
+const MAX_RAY_DISTANCE: f32 = 25.0;
+const MIN_WALL_DISTANCE: f32 = 0.01;
+const MIN_SPRITE_DEPTH: f32 = 0.1;
 
-pub struct RayCaster {
-    z_buffer: [f32; RAY_COUNT],
-    coin_texture: Option<Texture2D>,
-    wall_texture: Option<Texture2D>,
-    floor_texture: Option<Texture2D>,
-    // Pre-create a render texture for better performance
-    wall_strip_texture: Option<RenderTexture2D>,
+// Projection shared by walls, floor and sprites: the view direction plus a
+// camera plane perpendicular to it whose half-length is tan(fov / 2)
+struct Camera {
+    x: f32,
+    y: f32,
+    dir_x: f32,
+    dir_y: f32,
+    plane_x: f32,
+    plane_y: f32,
+    // Pixels per world unit at distance 1
+    focal: f32,
+    screen_width: f32,
 }
 
-impl RayCaster {
-    pub fn new() -> Self {
+impl Camera {
+    fn new(player: &Player, screen_width: usize) -> Self {
+        let (dir_x, dir_y) = player.get_direction();
+        let (plane_x, plane_y) = player.get_camera_plane();
+
         Self {
-            z_buffer: [0.0; RAY_COUNT],
-            coin_texture: None,
-            wall_texture: None,
-            floor_texture: None,
-            wall_strip_texture: None,
+            x: player.x,
+            y: player.y,
+            dir_x,
+            dir_y,
+            plane_x,
+            plane_y,
+            focal: (screen_width as f32 / 2.0) / (player.fov / 2.0).tan(),
+            screen_width: screen_width as f32,
         }
     }
 
-    pub fn load_textures(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) {
-        // Load coin texture (keeping existing logic)
-        let coin_texture_paths = [
-            "assets/sprites/sprite.png",
-            "assets/sprites/coin.png",
-            "assets/coin.png",
-        ];
-
-        for path in &coin_texture_paths {
-            match rl.load_texture(thread, path) {
-                Ok(texture) => {
-                    println!("Loaded coin texture from: {}", path);
-                    self.coin_texture = Some(texture);
-                    break;
-                }
-                Err(_) => continue,
-            }
-        }
+    // Screen x and perpendicular depth of a world point, or None when it is behind the camera
+    fn project(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        let dx = x - self.x;
+        let dy = y - self.y;
 
-        // Load wall texture
-        let wall_texture_paths = [
-            "assets/textures/dungeon.jpg",
-            "assets/textures/iceDungeon.jpg",
-            "dungeon.jpg",
-        ];
-
-        for path in &wall_texture_paths {
-            match rl.load_texture(thread, path) {
-                Ok(texture) => {
-                    println!("Loaded wall texture from: {}", path);
-                    self.wall_texture = Some(texture);
-                    break;
-                }
-                Err(e) => {
-                    println!("Failed to load wall texture from {}: {}", path, e);
-                    continue;
-                }
-            }
+        // Inverse of the [plane dir] camera matrix
+        let inv_det = 1.0 / (self.plane_x * self.dir_y - self.dir_x * self.plane_y);
+        let transform_x = inv_det * (self.dir_y * dx - self.dir_x * dy);
+        let transform_y = inv_det * (-self.plane_y * dx + self.plane_x * dy);
+
+        if transform_y <= MIN_SPRITE_DEPTH {
+            return None;
         }
 
-        // Load floor texture
-        let floor_texture_paths = [
-            "assets/textures/ground.jpg",
-            "assets/textures/Ground2.jpg",
-            "ground.jpg",
-        ];
-
-        for path in &floor_texture_paths {
-            match rl.load_texture(thread, path) {
-                Ok(texture) => {
-                    println!("Loaded floor texture from: {}", path);
-                    self.floor_texture = Some(texture);
-                    break;
-                }
-                Err(e) => {
-                    println!("Failed to load floor texture from {}: {}", path, e);
-                    continue;
-                }
-            }
+        Some(((self.screen_width / 2.0) * (1.0 + transform_x / transform_y), transform_y))
+    }
+}
+
+// Where a ray stopped. `distance` is perpendicular to the camera plane and
+// `wall_x` is the hit position along the wall face, in 0..1
+struct RayHit {
+    distance: f32,
+    wall_type: u8,
+    wall_x: f32,
+    is_vertical_wall: bool,
+}
+
+// The cell of a sprite sheet drawn this frame
+struct SpriteFrame<'a> {
+    texture: &'a TextureData,
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> SpriteFrame<'a> {
+    fn new(texture: &'a TextureData, sheet: &SpriteSheet, column: usize, row: usize) -> Self {
+        let width = (texture.width / sheet.columns).max(1);
+        let height = (texture.height / sheet.rows).max(1);
+
+        Self {
+            texture,
+            left: column.min(sheet.columns - 1) * width,
+            top: row.min(sheet.rows - 1) * height,
+            width,
+            height,
         }
+    }
+
+    // `x`, `y` are within the frame
+    fn get_pixel(&self, x: usize, y: usize) -> u32 {
+        self.texture.get_pixel(self.left + x.min(self.width - 1), self.top + y.min(self.height - 1))
+    }
+}
 
-        // Create a small render texture for wall strips
-        match rl.load_render_texture(thread, 1, SCREEN_HEIGHT as u32) {
-            Ok(rt) => self.wall_strip_texture = Some(rt),
-            Err(e) => println!("Failed to create wall strip render texture: {}", e),
+// Software renderer: walls, floor, ceiling and sprites are all written into a
+// CPU framebuffer, which the front-end uploads as a single texture per frame
+pub struct RayCaster {
+    framebuffer: FrameBuffer,
+    z_buffer: Vec<f32>,
+    // Entity sprites and how they are cut into frames, keyed by path like
+    // the wall textures
+    sprite_textures: HashMap<String, (TextureData, SpriteSheet)>,
+    // Every wall texture named by a level file, keyed by path. Each map says
+    // which of these its wall types use
+    wall_textures: HashMap<String, TextureData>,
+    floor_texture: Option<TextureData>,
+}
+
+impl RayCaster {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            framebuffer: FrameBuffer::new(width, height),
+            z_buffer: vec![0.0; width],
+            sprite_textures: HashMap::new(),
+            wall_textures: HashMap::new(),
+            floor_texture: None,
         }
     }
 
-    pub fn render(&mut self, d: &mut RaylibDrawHandle, player: &Player, map: &Map, sprites: &SpriteManager) {
-        for i in 0..RAY_COUNT {
-            let camera_x = 2.0 * i as f32 / RAY_COUNT as f32 - 1.0;
-            let ray_angle = player.angle + camera_x * player.fov / 2.0;
-            
-            let (hit_distance, wall_type, wall_x, is_vertical_wall) = self.cast_ray_detailed(player, map, ray_angle);
-            
-            self.z_buffer[i] = hit_distance;
-            
-            // Calculate wall height and position
-            let wall_height = (SCREEN_HEIGHT as f32 / hit_distance).min(SCREEN_HEIGHT as f32);
-            let wall_start = ((SCREEN_HEIGHT as f32 - wall_height) / 2.0) as i32;
+    // New output size, keeping the loaded textures
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.framebuffer = FrameBuffer::new(width, height);
+        self.z_buffer = vec![0.0; width];
+    }
+
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        &self.framebuffer
+    }
+
+    pub fn into_framebuffer(self) -> FrameBuffer {
+        self.framebuffer
+    }
+
+    pub fn add_sprite_texture(&mut self, path: String, texture: TextureData, sheet: SpriteSheet) {
+        self.sprite_textures.insert(path, (texture, sheet));
+    }
+
+    pub fn set_floor_texture(&mut self, texture: TextureData) {
+        self.floor_texture = Some(texture);
+    }
+
+    pub fn add_wall_texture(&mut self, path: String, texture: TextureData) {
+        self.wall_textures.insert(path, texture);
+    }
+
+    pub fn render(&mut self, player: &Player, map: &Map, sprites: &SpriteManager) -> &FrameBuffer {
+        let width = self.framebuffer.width;
+        let height = self.framebuffer.height as i32;
+        let camera = Camera::new(player, width);
+
+        for i in 0..width {
+            let camera_x = 2.0 * i as f32 / width as f32 - 1.0;
+            let ray_dir_x = camera.dir_x + camera.plane_x * camera_x;
+            let ray_dir_y = camera.dir_y + camera.plane_y * camera_x;
+
+            let hit = self.cast_ray_detailed(player, map, ray_dir_x, ray_dir_y);
+
+            self.z_buffer[i] = hit.distance;
+
+            // Calculate wall height and position. Walls taller than the screen are
+            // left unclamped so their texture keeps the right proportions
+            let wall_height = camera.focal / hit.distance.max(MIN_WALL_DISTANCE);
+            let wall_start = ((height as f32 - wall_height) / 2.0) as i32;
             let wall_end = wall_start + wall_height as i32;
-            
+
             // Draw textured wall column
-            self.draw_wall_column(d, i as i32, wall_start, wall_end, wall_x, wall_height, hit_distance, wall_type, is_vertical_wall);
-            
+            let wall_texture = map.textures.get(&hit.wall_type).and_then(|path| self.wall_textures.get(path));
+            draw_wall_column(&mut self.framebuffer, i, wall_start, wall_height, &hit, wall_texture);
+
             // Draw floor and ceiling
-            self.draw_floor_and_ceiling(d, i as i32, wall_start, wall_end, player, ray_angle);
+            self.draw_floor_and_ceiling(i, wall_start, wall_end, &camera, (ray_dir_x, ray_dir_y));
         }
-        
-        self.draw_sprites(d, player, sprites);
+
+        self.draw_sprites(player, &camera, sprites);
+
+        &self.framebuffer
     }
 
-    fn cast_ray_detailed(&self, player: &Player, map: &Map, angle: f32) -> (f32, u8, f32, bool) {
-        let dx = angle.cos();
-        let dy = angle.sin();
-        
-        let mut ray_x = player.x;
-        let mut ray_y = player.y;
-        
-        let step = 0.005; // Smaller step for more precision
-        let mut distance = 0.0;
-        let mut last_x = ray_x;
-        let mut last_y = ray_y;
-        
-        loop {
-            last_x = ray_x;
-            last_y = ray_y;
-            ray_x += dx * step;
-            ray_y += dy * step;
-            distance += step;
-            
-            let grid_x = ray_x as usize;
-            let grid_y = ray_y as usize;
-            
-            if grid_x >= map.width || grid_y >= map.height {
-                return (distance, 1, 0.0, false);
+    // The first wall or closed part of a door along the ray. The direction is
+    // not normalized: with a ray built from the camera plane the returned
+    // distance is the perpendicular distance to the camera plane, which is what
+    // keeps straight walls straight (no fisheye)
+    fn cast_ray_detailed(&self, player: &Player, map: &Map, dx: f32, dy: f32) -> RayHit {
+        for step in GridWalk::new((player.x, player.y), (dx, dy)) {
+            let GridStep { cell: (map_x, map_y), distance, exit, is_vertical_wall } = step;
+
+            if distance > MAX_RAY_DISTANCE {
+                return RayHit {
+                    distance: MAX_RAY_DISTANCE,
+                    wall_type: 1,
+                    wall_x: 0.0,
+                    is_vertical_wall,
+                };
             }
-            
-            let wall_type = map.get_cell(grid_x, grid_y);
+
+            // Anything outside the grid counts as a wall of type 1
+            let wall_type = if map_x < 0 || map_y < 0 {
+                1
+            } else {
+                map.get_cell(map_x as usize, map_y as usize)
+            };
+
+            if wall_type == door::DOOR_CELL
+                && let Some(door) = map.door_at(map_x as usize, map_y as usize)
+            {
+                if let Some(hit) = door_hit(player, door, (dx, dy), distance, exit) {
+                    return hit;
+                }
+                continue;
+            }
+
             if wall_type > 0 {
-                // Determine if we hit a vertical or horizontal wall
-                let last_grid_x = last_x as usize;
-                let last_grid_y = last_y as usize;
-                
-                let is_vertical_wall = last_grid_x != grid_x;
-                
                 // Calculate texture coordinate
                 let wall_x = if is_vertical_wall {
-                    ray_y.fract()
+                    player.y + distance * dy
                 } else {
-                    ray_x.fract()
+                    player.x + distance * dx
                 };
-                
-                return (distance, wall_type, wall_x, is_vertical_wall);
-            }
-            
-            if distance > 25.0 {
-                break;
-            }
-        }
-        
-        (distance, 1, 0.0, false)
-    }
 
-    fn draw_wall_column(&self, d: &mut RaylibDrawHandle, x: i32, wall_start: i32, wall_end: i32, 
-                       wall_x: f32, wall_height: f32, distance: f32, wall_type: u8, is_vertical_wall: bool) {
-        
-        if let Some(wall_texture) = &self.wall_texture {
-            let tex_x = (wall_x * wall_texture.width as f32) as i32;
-            let tex_x = tex_x.max(0).min(wall_texture.width - 1);
-            
-            // Calculate brightness based on distance and wall orientation
-            let mut brightness = (1.0 / (1.0 + distance * 0.08)).min(1.0);
-            if !is_vertical_wall {
-                brightness *= 0.7; // Make horizontal walls slightly darker
+                return RayHit {
+                    distance,
+                    wall_type,
+                    wall_x: wall_x - wall_x.floor(),
+                    is_vertical_wall,
+                };
             }
-            
-            let tint = Color::new(
-                (255.0 * brightness) as u8,
-                (255.0 * brightness) as u8,
-                (255.0 * brightness) as u8,
-                255,
-            );
-            
-            // Draw textured wall strip
-            let wall_rect_height = (wall_end - wall_start).max(1);
-            
-            let source_rect = Rectangle::new(
-                tex_x as f32, 
-                0.0, 
-                1.0, 
-                wall_texture.height as f32
-            );
-            
-            let dest_rect = Rectangle::new(
-                x as f32, 
-                wall_start as f32, 
-                1.0, 
-                wall_rect_height as f32
-            );
-            
-            d.draw_texture_pro(
-                wall_texture,
-                source_rect,
-                dest_rect,
-                Vector2::zero(),
-                0.0,
-                tint
-            );
-        } else {
-            // Fallback to colored walls if no texture
-            let wall_color = self.get_wall_color(wall_type, distance);
-            d.draw_line(x, wall_start, x, wall_end, wall_color);
         }
+
+        unreachable!("a grid walk never ends")
     }
 
-    fn draw_floor_and_ceiling(&self, d: &mut RaylibDrawHandle, x: i32, wall_start: i32, wall_end: i32, 
-                             player: &Player, ray_angle: f32) {
-        
+    fn draw_floor_and_ceiling(&mut self, x: usize, wall_start: i32, wall_end: i32, camera: &Camera, ray_dir: (f32, f32)) {
+        let (ray_dir_x, ray_dir_y) = ray_dir;
+        let height = self.framebuffer.height as i32;
+
         // Draw ceiling
-        let ceiling_color = Color::new(30, 30, 60, 255);
-        d.draw_line(x, 0, x, wall_start.max(0), ceiling_color);
-        
+        let ceiling_color = rgba(30, 30, 60, 255);
+        self.framebuffer.draw_column(x, 0, wall_start, ceiling_color);
+
         // Draw floor
         if let Some(floor_texture) = &self.floor_texture {
-            self.draw_textured_floor(d, x, wall_end, player, ray_angle, floor_texture);
-        } else {
-            let floor_color = Color::new(60, 40, 30, 255);
-            d.draw_line(x, wall_end.max(0), x, SCREEN_HEIGHT, floor_color);
-        }
-    }
+            let floor_start = wall_end.max(height / 2).max(0);
 
-    fn draw_textured_floor(&self, d: &mut RaylibDrawHandle, x: i32, wall_end: i32, 
-                          player: &Player, ray_angle: f32, floor_texture: &Texture2D) {
-        
-        let floor_start = wall_end.max(SCREEN_HEIGHT / 2);
-        let step_size = 2; // Draw every 2nd pixel for performance
-        
-        for y in (floor_start..SCREEN_HEIGHT).step_by(step_size) {
-            let distance_to_floor = (SCREEN_HEIGHT as f32 / 2.0 - y as f32);
-            if distance_to_floor.abs() < 1.0 {
-                continue;
-            }
-            
-            let row_distance = (SCREEN_HEIGHT as f32 / 2.0) / distance_to_floor.abs();
-            
-            if row_distance > 0.0 && row_distance < 25.0 {
-                let floor_x = player.x + ray_angle.cos() * row_distance;
-                let floor_y = player.y + ray_angle.sin() * row_distance;
-                
-                let tex_x = ((floor_x * floor_texture.width as f32) as i32 % floor_texture.width).abs();
-                let tex_y = ((floor_y * floor_texture.height as f32) as i32 % floor_texture.height).abs();
-                
-                let brightness = (1.0 / (1.0 + row_distance * 0.15)).min(0.8);
-                let tint = Color::new(
-                    (255.0 * brightness) as u8,
-                    (255.0 * brightness) as u8,
-                    (255.0 * brightness) as u8,
-                    255,
-                );
-                
-                // Draw a small rectangle from the texture instead of just a pixel
-                let source_rect = Rectangle::new(tex_x as f32, tex_y as f32, 1.0, 1.0);
-                let dest_rect = Rectangle::new(x as f32, y as f32, 1.0, step_size as f32);
-                
-                d.draw_texture_pro(
-                    floor_texture,
-                    source_rect,
-                    dest_rect,
-                    Vector2::zero(),
-                    0.0,
-                    tint
-                );
+            for y in floor_start..height {
+                let distance_to_floor = y as f32 - height as f32 / 2.0;
+                if distance_to_floor < 1.0 {
+                    continue;
+                }
+
+                // Perpendicular distance of this row, with the camera at half a wall's height
+                let row_distance = (camera.focal * 0.5) / distance_to_floor;
+
+                let color = if row_distance < MAX_RAY_DISTANCE {
+                    let floor_x = camera.x + ray_dir_x * row_distance;
+                    let floor_y = camera.y + ray_dir_y * row_distance;
+
+                    let tex_x = (floor_x.rem_euclid(1.0) * floor_texture.width as f32) as usize;
+                    let tex_y = (floor_y.rem_euclid(1.0) * floor_texture.height as f32) as usize;
+
+                    let brightness = (1.0 / (1.0 + row_distance * 0.15)).min(0.8);
+                    shade(floor_texture.get_pixel(tex_x, tex_y), brightness)
+                } else {
+                    rgba(0, 0, 0, 255)
+                };
+
+                self.framebuffer.set_pixel(x, y as usize, color);
             }
+        } else {
+            let floor_color = rgba(60, 40, 30, 255);
+            self.framebuffer.draw_column(x, wall_end, height, floor_color);
         }
     }
 
-    fn get_wall_color(&self, wall_type: u8, distance: f32) -> Color {
-        let base_color = match wall_type {
-            1 => Color::RED,
-            2 => Color::GREEN,
-            3 => Color::BLUE,
-            4 => Color::YELLOW,
-            5 => Color::PURPLE,
-            _ => Color::GRAY,
-        };
-        
-        let brightness = (1.0 / (1.0 + distance * 0.1)).min(1.0);
-        Color::new(
-            (base_color.r as f32 * brightness) as u8,
-            (base_color.g as f32 * brightness) as u8,
-            (base_color.b as f32 * brightness) as u8,
-            255,
-        )
-    }
+    fn draw_sprites(&mut self, player: &Player, camera: &Camera, sprites: &SpriteManager) {
+        let mut sprite_distances: Vec<(&Entity, f32)> = Vec::new();
 
-    fn draw_sprites(&self, d: &mut RaylibDrawHandle, player: &Player, sprites: &SpriteManager) {
-        let mut sprite_distances: Vec<(usize, f32)> = Vec::new();
-        
-        for (i, coin) in sprites.coins.iter().enumerate() {
-            if !coin.collected {
-                let dx = coin.x - player.x;
-                let dy = coin.y - player.y;
-                let distance = (dx * dx + dy * dy).sqrt();
-                if distance < 15.0 {
-                    sprite_distances.push((i, distance));
-                }
+        for entity in sprites.entities.iter().filter(|entity| entity.active) {
+            let dx = entity.x - player.x;
+            let dy = entity.y - player.y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance < 15.0 {
+                sprite_distances.push((entity, distance));
             }
         }
-        
+
         sprite_distances.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        
-        for (sprite_idx, distance) in sprite_distances {
-            let coin = &sprites.coins[sprite_idx];
-            if let Some(texture) = &self.coin_texture {
-                self.draw_texture_sprite(d, player, coin.x, coin.y, distance, texture, sprites.get_animation_scale());
-            } else {
-                self.draw_circle_sprite(d, player, coin.x, coin.y, distance, Color::GOLD, sprites.get_animation_scale());
+
+        for (entity, distance) in sprite_distances {
+            let scale = entity.scale * entity.animation_scale();
+            match self.sprite_textures.get(&entity.sprite) {
+                Some((texture, sheet)) => {
+                    let column = sheet.frame(entity.animation_time);
+                    let row = sheet.direction(entity.angle, player.angle);
+                    let frame = SpriteFrame::new(texture, sheet, column, row);
+                    draw_texture_sprite(&mut self.framebuffer, &self.z_buffer, camera, entity, distance, &frame, scale);
+                }
+                None => draw_circle_sprite(&mut self.framebuffer, &self.z_buffer, camera, entity, distance, entity.color, scale),
             }
         }
     }
+}
 
-    fn draw_texture_sprite(&self, d: &mut RaylibDrawHandle, player: &Player, sprite_x: f32, sprite_y: f32, distance: f32, texture: &Texture2D, scale: f32) {
-        let dx = sprite_x - player.x;
-        let dy = sprite_y - player.y;
-        
-        let cos_angle = player.angle.cos();
-        let sin_angle = player.angle.sin();
-        
-        let transform_x = dy * cos_angle - dx * sin_angle;
-        let transform_y = dx * cos_angle + dy * sin_angle;
-        
-        if transform_y <= 0.1 {
-            return;
-        }
-        
-        let sprite_screen_x = (SCREEN_WIDTH as f32 / 2.0) * (1.0 + transform_x / transform_y);
-        
-        let base_sprite_height = (SCREEN_HEIGHT as f32 / transform_y) * 0.5;
-        let sprite_height = (base_sprite_height * scale).abs();
-        let sprite_width = sprite_height;
-        
-        if sprite_screen_x < -sprite_width || sprite_screen_x > SCREEN_WIDTH as f32 + sprite_width {
-            return;
+// A door is a slab across the middle of its cell, set back from the walls on
+// either side, that slides into the wall as it opens. The ray hits it when it
+// crosses the slab's line between entering (`enter`) and leaving (`exit`) the
+// cell, on the part that hasn't slid away yet.
+fn door_hit(player: &Player, door: &Door, (dx, dy): (f32, f32), enter: f32, exit: f32) -> Option<RayHit> {
+    // Across and along the slab
+    let (origin, along_origin, dir, dir_along, cell, cell_along) = if door.vertical {
+        (player.x, player.y, dx, dy, door.x, door.y)
+    } else {
+        (player.y, player.x, dy, dx, door.y, door.x)
+    };
+    if dir == 0.0 {
+        return None;
+    }
+
+    let distance = (cell as f32 + 0.5 - origin) / dir;
+    if distance < enter || distance > exit {
+        return None;
+    }
+    let along = along_origin + distance * dir_along - cell_along as f32;
+    if along < door.open_amount {
+        return None;
+    }
+
+    Some(RayHit {
+        distance,
+        wall_type: door::DOOR_CELL,
+        // The texture moves with the slab
+        wall_x: along - door.open_amount,
+        is_vertical_wall: door.vertical,
+    })
+}
+
+fn draw_wall_column(framebuffer: &mut FrameBuffer, x: usize, wall_start: i32, wall_height: f32,
+                    hit: &RayHit, wall_texture: Option<&TextureData>) {
+    let wall_end = wall_start + wall_height as i32;
+
+    if let Some(wall_texture) = wall_texture {
+        let tex_x = ((hit.wall_x * wall_texture.width as f32) as usize).min(wall_texture.width - 1);
+
+        // Calculate brightness based on distance and wall orientation
+        let mut brightness = (1.0 / (1.0 + hit.distance * 0.08)).min(1.0);
+        if !hit.is_vertical_wall {
+            brightness *= 0.7; // Make horizontal walls slightly darker
         }
-        
-        let draw_x = sprite_screen_x - sprite_width / 2.0;
-        let draw_y = (SCREEN_HEIGHT as f32 - sprite_height) / 2.0;
-        
-        let brightness = (1.0 / (1.0 + distance * 0.05)).min(1.0);
-        let tint = Color::new(
-            (255.0 * brightness) as u8,
-            (255.0 * brightness) as u8,
-            (255.0 * brightness) as u8,
-            255,
-        );
-        
-        let center_x = sprite_screen_x as i32;
-        if center_x >= 0 && center_x < RAY_COUNT as i32 && transform_y < self.z_buffer[center_x as usize] {
-            d.draw_texture_ex(
-                texture,
-                Vector2::new(draw_x, draw_y),
-                0.0,
-                sprite_width / texture.width as f32,
-                tint,
-            );
+
+        // Only the visible part of the strip is sampled
+        let y_start = wall_start.max(0);
+        let y_end = wall_end.min(framebuffer.height as i32);
+        let tex_step = wall_texture.height as f32 / wall_height;
+
+        for y in y_start..y_end {
+            let tex_y = ((y - wall_start) as f32 * tex_step) as usize;
+            let color = shade(wall_texture.get_pixel(tex_x, tex_y), brightness);
+            framebuffer.set_pixel(x, y as usize, color);
         }
+    } else {
+        // Fallback to colored walls for wall types without a texture
+        let wall_color = get_wall_color(hit.wall_type, hit.distance);
+        framebuffer.draw_column(x, wall_start, wall_end, wall_color);
+    }
+}
+
+fn get_wall_color(wall_type: u8, distance: f32) -> u32 {
+    let base_color = match wall_type {
+        1 => rgba(230, 41, 55, 255),   // red
+        2 => rgba(0, 228, 48, 255),    // green
+        3 => rgba(0, 121, 241, 255),   // blue
+        4 => rgba(253, 249, 0, 255),   // yellow
+        5 => rgba(200, 122, 255, 255), // purple
+        door::DOOR_CELL => rgba(127, 106, 79, 255), // brown
+        _ => rgba(130, 130, 130, 255), // gray
+    };
+
+    let brightness = (1.0 / (1.0 + distance * 0.1)).min(1.0);
+    shade(base_color, brightness)
+}
+
+fn draw_texture_sprite(framebuffer: &mut FrameBuffer, z_buffer: &[f32], camera: &Camera,
+                       entity: &Entity, distance: f32, frame: &SpriteFrame, scale: f32) {
+    let Some((sprite_screen_x, transform_y)) = camera.project(entity.x, entity.y) else {
+        return;
+    };
+
+    let base_sprite_height = (camera.focal / transform_y) * 0.5;
+    let sprite_height = (base_sprite_height * scale).abs();
+    let sprite_width = sprite_height;
+    let screen_width = framebuffer.width as f32;
+    let screen_height = framebuffer.height as f32;
+
+    if sprite_screen_x < -sprite_width || sprite_screen_x > screen_width + sprite_width {
+        return;
     }
 
-    fn draw_circle_sprite(&self, d: &mut RaylibDrawHandle, player: &Player, sprite_x: f32, sprite_y: f32, distance: f32, color: Color, scale: f32) {
-        let dx = sprite_x - player.x;
-        let dy = sprite_y - player.y;
-        
-        let cos_angle = player.angle.cos();
-        let sin_angle = player.angle.sin();
-        
-        let transform_x = dy * cos_angle - dx * sin_angle;
-        let transform_y = dx * cos_angle + dy * sin_angle;
-        
-        if transform_y <= 0.1 {
-            return;
+    // Frame keeps its aspect ratio, scaled to the sprite width
+    let pixel_scale = sprite_width / frame.width as f32;
+    let draw_height = frame.height as f32 * pixel_scale;
+    let draw_x = sprite_screen_x - sprite_width / 2.0;
+    // Wall heights are `focal / depth` pixels, so the offset scales the same way
+    let lift = entity.vertical_offset * camera.focal / transform_y;
+    let draw_y = (screen_height - sprite_height) / 2.0 - lift;
+
+    let brightness = (1.0 / (1.0 + distance * 0.05)).min(1.0);
+
+    let x_start = (draw_x as i32).max(0);
+    let x_end = ((draw_x + sprite_width) as i32).min(framebuffer.width as i32);
+    let y_start = (draw_y as i32).max(0);
+    let y_end = ((draw_y + draw_height) as i32).min(framebuffer.height as i32);
+
+    // One vertical strip per column, drawn only where the sprite is nearer
+    // than the wall in that column, so a sprite partly behind a wall is cut
+    // at the wall's edge
+    for x in x_start..x_end {
+        if transform_y >= z_buffer[x as usize] {
+            continue;
         }
-        
-        let sprite_screen_x = (SCREEN_WIDTH as f32 / 2.0) * (1.0 + transform_x / transform_y);
-        
-        let base_sprite_height = (SCREEN_HEIGHT as f32 / transform_y) * 0.5;
-        let sprite_height = (base_sprite_height * scale).abs();
-        let sprite_width = sprite_height * 0.8;
-        
-        if sprite_screen_x < -sprite_width || sprite_screen_x > SCREEN_WIDTH as f32 + sprite_width {
-            return;
+        let tex_x = ((x as f32 - draw_x) / pixel_scale) as usize;
+
+        for y in y_start..y_end {
+            let tex_y = ((y as f32 - draw_y) / pixel_scale) as usize;
+            let color = frame.get_pixel(tex_x, tex_y);
+            framebuffer.blend_pixel(x as usize, y as usize, shade(color, brightness));
         }
-        
-        let draw_start_x = (sprite_screen_x - sprite_width / 2.0) as i32;
-        let draw_end_x = (sprite_screen_x + sprite_width / 2.0) as i32;
-        let draw_start_y = ((SCREEN_HEIGHT as f32 - sprite_height) / 2.0) as i32;
-        let draw_end_y = draw_start_y + sprite_height as i32;
-        
-        let center_x = sprite_screen_x as i32;
-        let center_y = (draw_start_y + draw_end_y) / 2;
-        let radius_squared = ((sprite_height / 2.0) as i32).pow(2) as f32;
-        let brightness = (1.0 / (1.0 + distance * 0.05)).min(1.0);
-        let final_color = Color::new(
-            (color.r as f32 * brightness) as u8,
-            (color.g as f32 * brightness) as u8,
-            (color.b as f32 * brightness) as u8,
-            255,
-        );
-        
-        let x_start = draw_start_x.max(0);
-        let x_end = draw_end_x.min(SCREEN_WIDTH);
-        let y_start = draw_start_y.max(0);
-        let y_end = draw_end_y.min(SCREEN_HEIGHT);
-        
-        for x in x_start..x_end {
-            if x >= 0 && x < RAY_COUNT as i32 && transform_y < self.z_buffer[x as usize] {
-                let dx_col = (x - center_x).pow(2) as f32;
-                
-                for y in y_start..y_end {
-                    let dy_row = (y - center_y).pow(2) as f32;
-                    let dist_from_center_squared = dx_col + dy_row;
-                    
-                    if dist_from_center_squared <= radius_squared {
-                        d.draw_pixel(x, y, final_color);
-                    }
+    }
+}
+
+fn draw_circle_sprite(framebuffer: &mut FrameBuffer, z_buffer: &[f32], camera: &Camera,
+                      entity: &Entity, distance: f32, color: u32, scale: f32) {
+    let Some((sprite_screen_x, transform_y)) = camera.project(entity.x, entity.y) else {
+        return;
+    };
+
+    let base_sprite_height = (camera.focal / transform_y) * 0.5;
+    let sprite_height = (base_sprite_height * scale).abs();
+    let sprite_width = sprite_height * 0.8;
+    let screen_width = framebuffer.width as i32;
+    let screen_height = framebuffer.height as i32;
+
+    if sprite_screen_x < -sprite_width || sprite_screen_x > screen_width as f32 + sprite_width {
+        return;
+    }
+
+    let draw_start_x = (sprite_screen_x - sprite_width / 2.0) as i32;
+    let draw_end_x = (sprite_screen_x + sprite_width / 2.0) as i32;
+    let lift = entity.vertical_offset * camera.focal / transform_y;
+    let draw_start_y = ((screen_height as f32 - sprite_height) / 2.0 - lift) as i32;
+    let draw_end_y = draw_start_y + sprite_height as i32;
+
+    let center_x = sprite_screen_x as i32;
+    let center_y = (draw_start_y + draw_end_y) / 2;
+    let radius_squared = ((sprite_height / 2.0) as i32).pow(2) as f32;
+    let brightness = (1.0 / (1.0 + distance * 0.05)).min(1.0);
+    let final_color = shade(color, brightness);
+
+    let x_start = draw_start_x.max(0);
+    let x_end = draw_end_x.min(screen_width);
+    let y_start = draw_start_y.max(0);
+    let y_end = draw_end_y.min(screen_height);
+
+    for x in x_start..x_end {
+        if transform_y < z_buffer[x as usize] {
+            let dx_col = (x - center_x).pow(2) as f32;
+
+            for y in y_start..y_end {
+                let dy_row = (y - center_y).pow(2) as f32;
+                let dist_from_center_squared = dx_col + dy_row;
+
+                if dist_from_center_squared <= radius_squared {
+                    framebuffer.set_pixel(x as usize, y as usize, final_color);
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+
+    fn level(number: usize) -> Map {
+        Map::load(Path::new(&format!("assets/levels/level{}.level", number))).unwrap()
+    }
+
+    // Distances are in multiples of `dir`, which needn't be normalized
+    fn cast(map: &Map, x: f32, y: f32, dir: (f32, f32)) -> RayHit {
+        RayCaster::new(1, 1).cast_ray_detailed(&Player::new(x, y, 0.0), map, dir.0, dir.1)
+    }
+
+    fn assert_hit(hit: RayHit, distance: f32, wall_type: u8, is_vertical_wall: bool, wall_x: f32) {
+        assert!((hit.distance - distance).abs() < 1e-4, "distance {} != {}", hit.distance, distance);
+        assert_eq!(hit.wall_type, wall_type);
+        assert_eq!(hit.is_vertical_wall, is_vertical_wall);
+        assert!((hit.wall_x - wall_x).abs() < 1e-4, "wall_x {} != {}", hit.wall_x, wall_x);
+    }
+
+    #[test]
+    fn axis_aligned_rays() {
+        let map = level(1);
+        // Across row 1 to the outer wall at x = 15
+        assert_hit(cast(&map, 1.5, 1.5, (1.0, 0.0)), 13.5, 1, true, 0.5);
+        // Down column 1 to the outer wall at y = 15
+        assert_hit(cast(&map, 1.5, 1.5, (0.0, 1.0)), 13.5, 1, false, 0.5);
+        // The type 2 wall at (2, 3), half a cell away
+        assert_hit(cast(&map, 1.5, 3.5, (1.0, 0.0)), 0.5, 2, true, 0.5);
+
+        let map = level(2);
+        // The type 3 block starting at (4, 1)
+        assert_hit(cast(&map, 1.5, 1.5, (1.0, 0.0)), 2.5, 3, true, 0.5);
+        // The outer walls west and north of the spawn
+        assert_hit(cast(&map, 1.5, 1.5, (-1.0, 0.0)), 0.5, 2, true, 0.5);
+        assert_hit(cast(&map, 1.5, 1.25, (0.0, -1.0)), 0.25, 2, false, 0.5);
+    }
+
+    #[test]
+    fn diagonal_rays() {
+        let map = level(1);
+        // Through (2, 1), then onto the top face of the type 2 wall at (2, 2)
+        // at t = 0.75, x = 2.25
+        assert_hit(cast(&map, 1.5, 1.25, (1.0, 1.0)), 0.75, 2, false, 0.25);
+
+        let map = level(2);
+        // x = 1.5 + 2t, y = 1.5 + t crosses (2, 1), (2, 2), (3, 2), (4, 2),
+        // (4, 3) and (5, 3), then hits the west face of (6, 3) at
+        // t = 2.25, y = 3.75
+        assert_hit(cast(&map, 1.5, 1.5, (2.0, 1.0)), 2.25, 3, true, 0.75);
+    }
+
+    // The wall at (2, 2) in level 1 has open cells to its west (1, 2) and
+    // north (2, 1). Rays passing either side of its top-left corner must hit
+    // the face they actually reach.
+    #[test]
+    fn rays_beside_a_corner_hit_the_right_face() {
+        let map = level(1);
+        // Crosses y = 2 first at x = 1.917, into the open (1, 2), then the
+        // west face at t = 0.5, y = 2.1
+        assert_hit(cast(&map, 1.5, 1.5, (1.0, 1.2)), 0.5, 2, true, 0.1);
+        // Crosses x = 2 first at y = 1.917, into the open (2, 1), then the
+        // top face at t = 0.5, x = 2.1
+        assert_hit(cast(&map, 1.5, 1.5, (1.2, 1.0)), 0.5, 2, false, 0.1);
+    }
+
+    #[test]
+    fn ray_through_a_corner_stops_on_it() {
+        let map = level(1);
+        let hit = cast(&map, 1.5, 1.5, (1.0, 1.0));
+        assert!((hit.distance - 0.5).abs() < 1e-4);
+        assert_eq!(hit.wall_type, 2);
+        // On the edge of whichever face it is given
+        assert!(hit.wall_x < 1e-4 || hit.wall_x > 1.0 - 1e-4);
+    }
+}
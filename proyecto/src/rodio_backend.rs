@@ -0,0 +1,170 @@
+use rodio::buffer::SamplesBuffer;
+use rodio::source::{Buffered, ChannelVolume};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use std::collections::HashMap;
+use std::error::Error;
+use std::f32::consts::FRAC_PI_4;
+use std::fs::File;
+use std::io::BufReader;
+use proyecto::audio::{AudioBackend, AudioManager, SoundEffect, MUSIC_CHANNELS};
+
+// How many one-shot effects can play at the same time
+const SFX_CHANNELS: usize = 8;
+
+// Audio through the default output device
+pub struct RodioBackend {
+    _stream: OutputStream,
+    stream_handle: OutputStreamHandle,
+    // One sink per music channel, so a track can fade out under the next
+    music_sinks: [Option<Sink>; MUSIC_CHANNELS],
+    music_paused: bool,
+    // Decoded up front so playing an effect never touches the disk. Buffered
+    // sources share their samples, so each play is a cheap clone
+    sounds: HashMap<SoundEffect, Buffered<SamplesBuffer<i16>>>,
+    sfx_sinks: Vec<Sink>,
+    next_sfx_sink: usize,
+}
+
+impl RodioBackend {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let (_stream, stream_handle) = OutputStream::try_default()?;
+
+        let mut sfx_sinks = Vec::new();
+        for _ in 0..SFX_CHANNELS {
+            sfx_sinks.push(Sink::try_new(&stream_handle)?);
+        }
+
+        Ok(Self {
+            _stream,
+            stream_handle,
+            music_sinks: Default::default(),
+            music_paused: false,
+            sounds: HashMap::new(),
+            sfx_sinks,
+            next_sfx_sink: 0,
+        })
+    }
+
+    // An idle effect channel. When every channel is busy they are cut off in
+    // turn, round robin, so rapid pickups never queue up behind each other
+    fn free_sfx_sink(&mut self) -> Option<&Sink> {
+        if let Some(index) = self.sfx_sinks.iter().position(|sink| sink.empty()) {
+            return Some(&self.sfx_sinks[index]);
+        }
+
+        let index = self.next_sfx_sink;
+        self.next_sfx_sink = (index + 1) % self.sfx_sinks.len();
+        match Sink::try_new(&self.stream_handle) {
+            Ok(sink) => std::mem::replace(&mut self.sfx_sinks[index], sink).stop(),
+            Err(e) => {
+                println!("Failed to create sound effect channel: {}", e);
+                return None;
+            }
+        }
+        Some(&self.sfx_sinks[index])
+    }
+}
+
+// The game's audio: rodio when there is an output device, otherwise a silent
+// manager so the game still runs on machines without sound
+pub fn open_audio() -> AudioManager {
+    match RodioBackend::new() {
+        Ok(backend) => {
+            println!("Audio system initialized successfully!");
+            AudioManager::new(Box::new(backend))
+        }
+        Err(e) => {
+            println!("Failed to initialize audio system, continuing without sound: {}", e);
+            AudioManager::silent()
+        }
+    }
+}
+
+impl AudioBackend for RodioBackend {
+    fn load_sound(&mut self, effect: SoundEffect, path: &str) -> Result<(), Box<dyn Error>> {
+        let source = Decoder::new(BufReader::new(File::open(path)?))?;
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+        let samples: Vec<i16> = source.collect();
+
+        self.sounds.insert(effect, SamplesBuffer::new(channels, sample_rate, samples).buffered());
+        Ok(())
+    }
+
+    fn play_sound(&mut self, effect: SoundEffect, volume: f32) {
+        let Some(buffer) = self.sounds.get(&effect).cloned() else {
+            return;
+        };
+        if let Some(sink) = self.free_sfx_sink() {
+            sink.set_volume(volume);
+            sink.append(buffer);
+        }
+    }
+
+    // Equal-power pan: the sound is mixed down to mono and sent to the left
+    // and right channels with gains that keep the total loudness constant
+    fn play_sound_at(&mut self, effect: SoundEffect, volume: f32, pan: f32) {
+        let Some(buffer) = self.sounds.get(&effect).cloned() else {
+            return;
+        };
+        let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+        if let Some(sink) = self.free_sfx_sink() {
+            sink.set_volume(volume);
+            sink.append(ChannelVolume::new(buffer, vec![angle.cos(), angle.sin()]));
+        }
+    }
+
+    fn play_music(&mut self, channel: usize, path: &str, looping: bool, volume: f32) -> Result<(), Box<dyn Error>> {
+        let source = Decoder::new(BufReader::new(File::open(path)?))?;
+        let sink = Sink::try_new(&self.stream_handle)?;
+
+        sink.set_volume(volume);
+        if looping {
+            sink.append(source.repeat_infinite());
+        } else {
+            sink.append(source);
+        }
+        if self.music_paused {
+            sink.pause();
+        }
+
+        if let Some(old) = self.music_sinks[channel].replace(sink) {
+            old.stop();
+        }
+        Ok(())
+    }
+
+    fn set_music_volume(&mut self, channel: usize, volume: f32) {
+        if let Some(ref sink) = self.music_sinks[channel] {
+            sink.set_volume(volume);
+        }
+    }
+
+    fn stop_music(&mut self, channel: usize) {
+        if let Some(sink) = self.music_sinks[channel].take() {
+            sink.stop();
+        }
+    }
+
+    fn music_finished(&self, channel: usize) -> bool {
+        self.music_sinks[channel].as_ref().is_some_and(|sink| sink.empty())
+    }
+
+    fn pause_music(&mut self) {
+        self.music_paused = true;
+        for sink in self.music_sinks.iter().flatten() {
+            sink.pause();
+        }
+    }
+
+    fn resume_music(&mut self) {
+        self.music_paused = false;
+        for sink in self.music_sinks.iter().flatten() {
+            sink.play();
+        }
+    }
+
+    fn is_music_playing(&self) -> bool {
+        !self.music_paused && self.music_sinks.iter().flatten().any(|sink| !sink.empty())
+    }
+}
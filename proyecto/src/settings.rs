@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::config::{self, Entry};
+use crate::input::{Control, CONTROLS};
+use crate::player::{self, Player};
+
+pub const SETTINGS_PATH: &str = "settings.cfg";
+
+// Window sizes offered on the settings screen. Any size can be written in the
+// file by hand.
+pub const RESOLUTIONS: [(u32, u32); 5] = [
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1600, 900),
+    (1920, 1080),
+];
+// Fraction of the window size the 3D view is rendered at before being
+// stretched to fill it
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+pub const MAX_RENDER_SCALE: f32 = 1.0;
+pub const MIN_FOV: f32 = 45.0;
+pub const MAX_FOV: f32 = 120.0;
+pub const MIN_MOUSE_SENSITIVITY: f32 = 0.1;
+pub const MAX_MOUSE_SENSITIVITY: f32 = 5.0;
+const MIN_WIDTH: u32 = 320;
+const MIN_HEIGHT: u32 = 240;
+
+// Player preferences kept between runs. The window, renderer, player and
+// input all read their configuration from here.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    pub render_scale: f32,
+    // Horizontal field of view in degrees
+    pub fov: f32,
+    // Multiplier on the base mouse look speed
+    pub mouse_sensitivity: f32,
+    pub invert_mouse: bool,
+    // Key name for every `Control`, as raylib's `KEY_*` without the prefix
+    pub keys: HashMap<Control, String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            music_volume: 0.3,
+            sfx_volume: 0.8,
+            width: 1024,
+            height: 768,
+            fullscreen: false,
+            render_scale: 1.0,
+            fov: 60.0,
+            mouse_sensitivity: 1.0,
+            invert_mouse: false,
+            keys: CONTROLS
+                .iter()
+                .map(|control| (*control, control.default_key().to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl Settings {
+    // A missing file gives the defaults; bad lines are reported and skipped so
+    // a hand-edited file never keeps the game from starting
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(source) => Self::parse(path, &source),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /*
+     * Same `key = value` format as the level headers, `#` starts a comment:
+     *
+     *   music_volume = 0.3
+     *   sfx_volume = 0.8
+     *   width = 1024
+     *   height = 768
+     *   fullscreen = false
+     *   render_scale = 0.5
+     *   fov = 60
+     *   mouse_sensitivity = 1.0
+     *   invert_mouse = false
+     *   key.forward = W
+     */
+    pub fn parse(path: &Path, source: &str) -> Self {
+        let mut settings = Self::default();
+        let path = path.display();
+
+        for line in config::lines(source) {
+            let Entry::Pair { key, value, .. } = line.entry else {
+                println!("{}:{}: expected `key = value`", path, line.number);
+                continue;
+            };
+
+            let number = |min: f32, max: f32| value.parse::<f32>().ok().map(|v| v.clamp(min, max));
+            let size = |min: u32| value.parse::<u32>().ok().map(|v| v.max(min));
+            let flag = || value.parse::<bool>().ok();
+            let parsed = match key {
+                "music_volume" => number(0.0, 1.0).map(|v| settings.music_volume = v),
+                "sfx_volume" => number(0.0, 1.0).map(|v| settings.sfx_volume = v),
+                "width" => size(MIN_WIDTH).map(|v| settings.width = v),
+                "height" => size(MIN_HEIGHT).map(|v| settings.height = v),
+                "fullscreen" => flag().map(|v| settings.fullscreen = v),
+                "render_scale" => number(MIN_RENDER_SCALE, MAX_RENDER_SCALE).map(|v| settings.render_scale = v),
+                "fov" => number(MIN_FOV, MAX_FOV).map(|v| settings.fov = v),
+                "mouse_sensitivity" => number(MIN_MOUSE_SENSITIVITY, MAX_MOUSE_SENSITIVITY)
+                    .map(|v| settings.mouse_sensitivity = v),
+                "invert_mouse" => flag().map(|v| settings.invert_mouse = v),
+                _ => match key.strip_prefix("key.").and_then(control_named) {
+                    // Through `bind` so a file from before a control existed,
+                    // or edited by hand, can't leave a key doing two things
+                    Some(control) if !value.is_empty() => {
+                        settings.bind(control, &value.to_ascii_uppercase());
+                        Some(())
+                    }
+                    Some(_) => None,
+                    None => {
+                        println!("{}:{}: unknown setting '{}'", path, line.number, key);
+                        continue;
+                    }
+                },
+            };
+            if parsed.is_none() {
+                println!("{}:{}: invalid value '{}' for {}", path, line.number, value, key);
+            }
+        }
+
+        settings
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "music_volume = {:.2}\nsfx_volume = {:.2}\nwidth = {}\nheight = {}\nfullscreen = {}\n\
+             render_scale = {:.2}\nfov = {:.0}\nmouse_sensitivity = {:.2}\ninvert_mouse = {}\n",
+            self.music_volume,
+            self.sfx_volume,
+            self.width,
+            self.height,
+            self.fullscreen,
+            self.render_scale,
+            self.fov,
+            self.mouse_sensitivity,
+            self.invert_mouse,
+        );
+        for control in CONTROLS {
+            text.push_str(&format!("key.{} = {}\n", control.config_name(), self.key(control)));
+        }
+        text
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn key(&self, control: Control) -> &str {
+        self.keys.get(&control).map_or(control.default_key(), |key| key.as_str())
+    }
+
+    // The keys of `controls` joined with '/', for help text
+    pub fn key_list(&self, controls: &[Control]) -> String {
+        controls.iter().map(|control| self.key(*control)).collect::<Vec<_>>().join("/")
+    }
+
+    // Binds `key` to `control`. A control that already used the key gets the
+    // old key of `control`, so no key ends up doing two things.
+    pub fn bind(&mut self, control: Control, key: &str) {
+        let old = self.key(control).to_string();
+        if let Some(other) = CONTROLS
+            .into_iter()
+            .find(|other| *other != control && self.key(*other) == key)
+        {
+            self.keys.insert(other, old);
+        }
+        self.keys.insert(control, key.to_string());
+    }
+
+    // Steps through `RESOLUTIONS` from the current size, or from the closest
+    // preset when the size was set by hand
+    pub fn step_resolution(&mut self, delta: i32) {
+        let pixels = self.width as i64 * self.height as i64;
+        let current = RESOLUTIONS
+            .iter()
+            .position(|size| *size == (self.width, self.height))
+            .unwrap_or_else(|| {
+                (0..RESOLUTIONS.len())
+                    .min_by_key(|i| (RESOLUTIONS[*i].0 as i64 * RESOLUTIONS[*i].1 as i64 - pixels).abs())
+                    .unwrap_or(0)
+            });
+        let next = (current as i32 + delta).clamp(0, RESOLUTIONS.len() as i32 - 1) as usize;
+        (self.width, self.height) = RESOLUTIONS[next];
+    }
+
+    // Size of the 3D view for a window of `width` x `height`
+    pub fn render_size(&self, width: u32, height: u32) -> (usize, usize) {
+        let scaled = |size: u32| ((size as f32 * self.render_scale).round() as usize).max(1);
+        (scaled(width), scaled(height))
+    }
+
+    // Field of view and mouse look for `player`
+    pub fn configure_player(&self, player: &mut Player) {
+        player.fov = self.fov.to_radians();
+        let direction = if self.invert_mouse { -1.0 } else { 1.0 };
+        player.mouse_sensitivity = player::MOUSE_SENSITIVITY * self.mouse_sensitivity * direction;
+    }
+}
+
+fn control_named(name: &str) -> Option<Control> {
+    CONTROLS.into_iter().find(|control| control.config_name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Settings {
+        Settings::parse(Path::new(SETTINGS_PATH), source)
+    }
+
+    #[test]
+    fn settings_survive_a_round_trip() {
+        let mut settings = Settings {
+            music_volume: 0.55,
+            sfx_volume: 0.0,
+            width: 1280,
+            height: 720,
+            fullscreen: true,
+            fov: 90.0,
+            mouse_sensitivity: 2.5,
+            invert_mouse: true,
+            ..Settings::default()
+        };
+        settings.bind(Control::Forward, "UP");
+
+        assert_eq!(parse(&settings.to_text()), settings);
+    }
+
+    #[test]
+    fn bad_lines_keep_the_defaults() {
+        let settings = parse("fov = wide\nwidth = 10\nmusic_volume = 3\nbogus\nkey.nothing = Q\n");
+
+        assert_eq!(settings.fov, Settings::default().fov);
+        assert_eq!(settings.width, MIN_WIDTH);
+        assert_eq!(settings.music_volume, 1.0);
+        assert_eq!(settings.keys, Settings::default().keys);
+    }
+
+    #[test]
+    fn loaded_bindings_never_share_a_key() {
+        // `use` was added later and defaults to E, which this file gave to restart
+        let settings = parse("key.restart = e\nkey.forward = Q\n");
+
+        assert_eq!(settings.key(Control::Restart), "E");
+        assert_eq!(settings.key(Control::Use), "R");
+        assert_eq!(settings.key(Control::Forward), "Q");
+        assert_eq!(settings.key(Control::Back), "S");
+        let mut keys: Vec<&str> = CONTROLS.iter().map(|control| settings.key(*control)).collect();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), CONTROLS.len());
+    }
+}
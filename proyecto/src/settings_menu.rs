@@ -0,0 +1,245 @@
+use crate::audio::{self, VOLUME_STEP};
+use crate::input::{Action, Control, PlayerCommand, CONTROLS};
+use crate::settings::{self, Settings};
+
+const RENDER_SCALE_STEP: f32 = 0.25;
+const FOV_STEP: f32 = 5.0;
+const SENSITIVITY_STEP: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsRow {
+    Resolution,
+    Fullscreen,
+    RenderScale,
+    Fov,
+    MouseSensitivity,
+    InvertMouse,
+    MusicVolume,
+    SfxVolume,
+    Key(Control),
+    Back,
+}
+
+// What a change on the settings screen needs the front-end to apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsChange {
+    // Window size, fullscreen or render scale
+    Video,
+    // Field of view or mouse look
+    Camera,
+    Audio,
+    Keys,
+    // The player picked "Back"
+    Back,
+}
+
+impl SettingsRow {
+    pub fn label(self) -> &'static str {
+        match self {
+            SettingsRow::Resolution => "Resolution",
+            SettingsRow::Fullscreen => "Fullscreen",
+            SettingsRow::RenderScale => "Render scale",
+            SettingsRow::Fov => "Field of view",
+            SettingsRow::MouseSensitivity => "Mouse sensitivity",
+            SettingsRow::InvertMouse => "Invert mouse",
+            SettingsRow::MusicVolume => "Music volume",
+            SettingsRow::SfxVolume => "Effects volume",
+            SettingsRow::Key(control) => control.label(),
+            SettingsRow::Back => "Back",
+        }
+    }
+
+    pub fn value(self, settings: &Settings) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" }.to_string();
+        match self {
+            SettingsRow::Resolution => format!("{}x{}", settings.width, settings.height),
+            SettingsRow::Fullscreen => on_off(settings.fullscreen),
+            SettingsRow::RenderScale => format!("{:.0}%", settings.render_scale * 100.0),
+            SettingsRow::Fov => format!("{:.0}°", settings.fov),
+            SettingsRow::MouseSensitivity => format!("{:.1}", settings.mouse_sensitivity),
+            SettingsRow::InvertMouse => on_off(settings.invert_mouse),
+            SettingsRow::MusicVolume => format!("{:.0}%", settings.music_volume * 100.0),
+            SettingsRow::SfxVolume => format!("{:.0}%", settings.sfx_volume * 100.0),
+            SettingsRow::Key(control) => settings.key(control).to_string(),
+            SettingsRow::Back => String::new(),
+        }
+    }
+}
+
+// The rows of the settings screen and which one is highlighted. Left/right
+// change a value, confirm toggles it or starts rebinding a key; the key
+// itself is read by the front-end, which knows the keyboard.
+pub struct SettingsMenu {
+    rows: Vec<SettingsRow>,
+    selected: usize,
+    // The control waiting for a key press, if any
+    rebinding: Option<Control>,
+}
+
+impl SettingsMenu {
+    pub fn new() -> Self {
+        let mut rows = vec![
+            SettingsRow::Resolution,
+            SettingsRow::Fullscreen,
+            SettingsRow::RenderScale,
+            SettingsRow::Fov,
+            SettingsRow::MouseSensitivity,
+            SettingsRow::InvertMouse,
+            SettingsRow::MusicVolume,
+            SettingsRow::SfxVolume,
+        ];
+        rows.extend(CONTROLS.iter().map(|control| SettingsRow::Key(*control)));
+        rows.push(SettingsRow::Back);
+
+        Self {
+            rows,
+            selected: 0,
+            rebinding: None,
+        }
+    }
+
+    pub fn rows(&self) -> &[SettingsRow] {
+        &self.rows
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn rebinding(&self) -> Option<Control> {
+        self.rebinding
+    }
+
+    // Ends a rebind started with confirm, binding `key` to the waiting control
+    pub fn finish_rebind(&mut self, settings: &mut Settings, key: &str) -> Option<SettingsChange> {
+        let control = self.rebinding.take()?;
+        settings.bind(control, key);
+        Some(SettingsChange::Keys)
+    }
+
+    pub fn update(&mut self, cmd: &PlayerCommand, settings: &mut Settings) -> Option<SettingsChange> {
+        // Back cancels a rebind, any other key is left to `finish_rebind`
+        if self.rebinding.is_some() {
+            if cmd.has(Action::Menu) {
+                self.rebinding = None;
+            }
+            return None;
+        }
+
+        if cmd.has(Action::MenuUp) && self.selected > 0 {
+            self.selected -= 1;
+        }
+        if cmd.has(Action::MenuDown) && self.selected + 1 < self.rows.len() {
+            self.selected += 1;
+        }
+
+        let mut step = 0;
+        if cmd.has(Action::MenuLeft) {
+            step -= 1;
+        }
+        if cmd.has(Action::MenuRight) {
+            step += 1;
+        }
+        let confirm = cmd.has(Action::Confirm);
+        if step == 0 && !confirm {
+            return None;
+        }
+        let delta = step as f32;
+
+        match self.rows[self.selected] {
+            SettingsRow::Resolution if step != 0 => {
+                settings.step_resolution(step);
+                Some(SettingsChange::Video)
+            }
+            SettingsRow::Fullscreen => {
+                settings.fullscreen = !settings.fullscreen;
+                Some(SettingsChange::Video)
+            }
+            SettingsRow::RenderScale if step != 0 => {
+                settings.render_scale = (settings.render_scale + delta * RENDER_SCALE_STEP)
+                    .clamp(settings::MIN_RENDER_SCALE, settings::MAX_RENDER_SCALE);
+                Some(SettingsChange::Video)
+            }
+            SettingsRow::Fov if step != 0 => {
+                settings.fov = (settings.fov + delta * FOV_STEP).clamp(settings::MIN_FOV, settings::MAX_FOV);
+                Some(SettingsChange::Camera)
+            }
+            SettingsRow::MouseSensitivity if step != 0 => {
+                settings.mouse_sensitivity = (settings.mouse_sensitivity + delta * SENSITIVITY_STEP)
+                    .clamp(settings::MIN_MOUSE_SENSITIVITY, settings::MAX_MOUSE_SENSITIVITY);
+                Some(SettingsChange::Camera)
+            }
+            SettingsRow::InvertMouse => {
+                settings.invert_mouse = !settings.invert_mouse;
+                Some(SettingsChange::Camera)
+            }
+            SettingsRow::MusicVolume if step != 0 => {
+                settings.music_volume = audio::step_volume(settings.music_volume, delta * VOLUME_STEP);
+                Some(SettingsChange::Audio)
+            }
+            SettingsRow::SfxVolume if step != 0 => {
+                settings.sfx_volume = audio::step_volume(settings.sfx_volume, delta * VOLUME_STEP);
+                Some(SettingsChange::Audio)
+            }
+            SettingsRow::Key(control) if confirm => {
+                self.rebinding = Some(control);
+                None
+            }
+            SettingsRow::Back if confirm => Some(SettingsChange::Back),
+            _ => None,
+        }
+    }
+}
+
+impl Default for SettingsMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pressed(action: Action) -> PlayerCommand {
+        PlayerCommand {
+            actions: vec![action],
+            ..PlayerCommand::default()
+        }
+    }
+
+    // Highlights the row for `control` and presses confirm on it
+    fn start_rebind(menu: &mut SettingsMenu, settings: &mut Settings, control: Control) {
+        let row = menu.rows().iter().position(|row| *row == SettingsRow::Key(control)).unwrap();
+        for _ in 0..row {
+            menu.update(&pressed(Action::MenuDown), settings);
+        }
+        assert_eq!(menu.update(&pressed(Action::Confirm), settings), None);
+        assert_eq!(menu.rebinding(), Some(control));
+    }
+
+    #[test]
+    fn a_rebind_binds_the_next_key() {
+        let mut menu = SettingsMenu::new();
+        let mut settings = Settings::default();
+        start_rebind(&mut menu, &mut settings, Control::Forward);
+
+        // Other commands wait for the key
+        assert_eq!(menu.update(&pressed(Action::MenuDown), &mut settings), None);
+        assert_eq!(menu.finish_rebind(&mut settings, "UP"), Some(SettingsChange::Keys));
+        assert_eq!(menu.rebinding(), None);
+        assert_eq!(settings.key(Control::Forward), "UP");
+    }
+
+    #[test]
+    fn back_cancels_a_rebind() {
+        let mut menu = SettingsMenu::new();
+        let mut settings = Settings::default();
+        start_rebind(&mut menu, &mut settings, Control::Forward);
+
+        assert_eq!(menu.update(&pressed(Action::Menu), &mut settings), None);
+        assert_eq!(menu.rebinding(), None);
+        assert_eq!(menu.finish_rebind(&mut settings, "UP"), None);
+        assert_eq!(settings, Settings::default());
+    }
+}
@@ -1,73 +1,97 @@
-use crate::player::Player;
+use crate::player::{self, Player};
 use crate::map::Map;
+use crate::entity::{Behaviour, Entity, EntityId};
 
-#[derive(Clone)]
-pub struct Coin {
-    pub x: f32,
-    pub y: f32,
-    pub collected: bool,
-}
-
+// Every entity of the level being played
 pub struct SpriteManager {
-    pub coins: Vec<Coin>,
-    animation_time: f32,
+    pub entities: Vec<Entity>,
+    next_id: EntityId,
+    // Seconds a pickup sparkle plays, see `entity::sparkle_lifetime`
+    pub sparkle_lifetime: f32,
 }
 
 impl SpriteManager {
-    pub fn new(map: &Map) -> Self {
-        let positions = map.get_coin_positions();
-        let mut coins = Vec::new();
-
-        for (x, y) in positions {
-            coins.push(Coin {
-                x,
-                y,
-                collected: false,
-            });
-        }
+    pub fn new(map: &Map, sparkle_lifetime: f32) -> Self {
+        let entities: Vec<Entity> = map
+            .entities
+            .iter()
+            .enumerate()
+            .map(|(id, placement)| Entity::from_placement(id as EntityId, placement))
+            .collect();
 
         Self {
-            coins,
-            animation_time: 0.0,
+            next_id: entities.len() as EntityId,
+            entities,
+            sparkle_lifetime,
         }
     }
 
-    pub fn check_collision(&mut self, player: &Player) -> Option<(f32, f32)> {
-        let collection_distance = 0.5;
+    // Deactivates the pickups the player is touching and returns what they
+    // were and where. Health packs are left alone while the player is at full
+    // health.
+    pub fn collect_pickups(&mut self, player: &Player) -> Vec<(Behaviour, f32, f32)> {
+        let mut collected = Vec::new();
+
+        for entity in &mut self.entities {
+            if !entity.active || !entity.behaviour.is_pickup() {
+                continue;
+            }
+            if entity.behaviour == Behaviour::HealthPack && player.health >= player::MAX_HEALTH {
+                continue;
+            }
+
+            let dx = entity.x - player.x;
+            let dy = entity.y - player.y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance < entity.radius + player.radius {
+                entity.active = false;
+                collected.push((entity.behaviour, entity.x, entity.y));
+            }
+        }
+
+        collected
+    }
 
-        for coin in &mut self.coins {
-            if !coin.collected {
-                let dx = coin.x - player.x;
-                let dy = coin.y - player.y;
-                let distance = (dx * dx + dy * dy).sqrt();
+    // Moves every enemy and returns the damage they dealt to the player
+    pub fn update_enemies(&mut self, dt: f32, player: &Player, map: &Map) -> i32 {
+        let mut damage = 0;
 
-                if distance < collection_distance {
-                    coin.collected = true;
-                    return Some((coin.x, coin.y));
-                }
+        for entity in &mut self.entities {
+            if let Some(enemy) = &mut entity.enemy {
+                damage += enemy.update(dt, player, map).unwrap_or(0);
+                (entity.x, entity.y, entity.angle) = (enemy.x, enemy.y, enemy.angle);
             }
         }
 
-        None
+        damage
     }
 
     pub fn all_coins_collected(&self) -> bool {
-        self.coins.iter().all(|coin| coin.collected)
+        self.coins().all(|coin| !coin.active)
     }
 
     pub fn coins_collected(&self) -> usize {
-        self.coins.iter().filter(|coin| coin.collected).count()
+        self.coins().filter(|coin| !coin.active).count()
     }
 
     pub fn total_coins(&self) -> usize {
-        self.coins.len()
+        self.coins().count()
     }
 
-    pub fn get_animation_scale(&self) -> f32 {
-        1.0 + (self.animation_time * 3.0).sin() * 0.1
+    // Plays a sparkle at (x, y), removed once it has finished
+    pub fn spawn_sparkle(&mut self, x: f32, y: f32) {
+        self.entities.push(Entity::sparkle(self.next_id, x, y, self.sparkle_lifetime));
+        self.next_id += 1;
+    }
+
+    fn coins(&self) -> impl Iterator<Item = &Entity> {
+        self.entities.iter().filter(|entity| entity.behaviour == Behaviour::Coin)
     }
 
     pub fn update_animation(&mut self, dt: f32) {
-        self.animation_time += dt;
+        for entity in &mut self.entities {
+            entity.update_animation(dt);
+        }
+        self.entities.retain(|entity| entity.active || entity.behaviour != Behaviour::Effect);
     }
 }
@@ -0,0 +1,242 @@
+use std::f32::consts::PI;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config::{self, Entry};
+
+const SHEET_EXTENSION: &str = "sheet";
+// One row per 45 degree turn
+pub const DIRECTIONS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopMode {
+    Loop,
+    // Stops on the last frame
+    Once,
+    // Plays forwards then backwards
+    PingPong,
+}
+
+// How a sprite image is cut into frames: a grid of `columns` x `rows` frames
+// of equal size. Columns are the animation frames. A directional sheet has
+// one row per direction, otherwise only the first row is used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteSheet {
+    pub columns: usize,
+    pub rows: usize,
+    // Seconds per frame
+    pub frame_duration: f32,
+    pub loop_mode: LoopMode,
+    pub directional: bool,
+}
+
+impl Default for SpriteSheet {
+    // A plain image, one still frame
+    fn default() -> Self {
+        Self {
+            columns: 1,
+            rows: 1,
+            frame_duration: 0.1,
+            loop_mode: LoopMode::Loop,
+            directional: false,
+        }
+    }
+}
+
+impl SpriteSheet {
+    // The layout for the sprite at `sprite_path`, read from the file next to
+    // it with the `.sheet` extension. Sprites without one are a single frame.
+    pub fn load_for(sprite_path: &str) -> Self {
+        let path = sheet_path(sprite_path);
+        match fs::read_to_string(&path) {
+            Ok(source) => Self::parse(&source).unwrap_or_else(|e| {
+                println!("{}: {}, using a single frame", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /*
+     * Same `key = value` format as the level headers:
+     *
+     *   columns = 8
+     *   rows = 8
+     *   frame_time = 0.12
+     *   loop = pingpong      # loop, once or pingpong
+     *   directional = true   # needs 8 rows
+     */
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut sheet = Self::default();
+
+        for line in config::lines(source) {
+            let Entry::Pair { key, value, .. } = line.entry else {
+                return Err(format!("line {}: expected `key = value`", line.number));
+            };
+
+            let parsed = match key {
+                "columns" => value.parse::<usize>().ok().filter(|n| *n > 0).map(|n| sheet.columns = n),
+                "rows" => value.parse::<usize>().ok().filter(|n| *n > 0).map(|n| sheet.rows = n),
+                "frame_time" => value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|time| *time > 0.0)
+                    .map(|time| sheet.frame_duration = time),
+                "loop" => match value {
+                    "loop" => Some(LoopMode::Loop),
+                    "once" => Some(LoopMode::Once),
+                    "pingpong" => Some(LoopMode::PingPong),
+                    _ => None,
+                }
+                .map(|mode| sheet.loop_mode = mode),
+                "directional" => value.parse::<bool>().ok().map(|v| sheet.directional = v),
+                _ => return Err(format!("line {}: unknown key '{}'", line.number, key)),
+            };
+            if parsed.is_none() {
+                return Err(format!("line {}: invalid value '{}' for {}", line.number, value, key));
+            }
+        }
+
+        if sheet.directional && sheet.rows != DIRECTIONS {
+            return Err(format!("a directional sheet needs {} rows, got {}", DIRECTIONS, sheet.rows));
+        }
+        Ok(sheet)
+    }
+
+    // Animation frame (column) shown `time` seconds into the animation
+    pub fn frame(&self, time: f32) -> usize {
+        let step = (time.max(0.0) / self.frame_duration) as usize;
+        let count = self.columns;
+
+        match self.loop_mode {
+            LoopMode::Loop => step % count,
+            LoopMode::Once => step.min(count - 1),
+            LoopMode::PingPong if count > 1 => {
+                let period = 2 * count - 2;
+                let position = step % period;
+                if position < count { position } else { period - position }
+            }
+            LoopMode::PingPong => 0,
+        }
+    }
+
+    // Seconds to play every frame once, until a `Once` animation is done
+    pub fn duration(&self) -> f32 {
+        self.columns as f32 * self.frame_duration
+    }
+
+    // Row to draw for an entity facing `facing` seen by a viewer looking
+    // along `view_angle`. Row 0 faces the viewer, and each row after it is
+    // turned 45 degrees further, so row 2 faces the viewer's left and row 4
+    // faces away.
+    pub fn direction(&self, facing: f32, view_angle: f32) -> usize {
+        if !self.directional {
+            return 0;
+        }
+
+        let relative = (facing - view_angle - PI).rem_euclid(2.0 * PI);
+        (relative / (2.0 * PI / DIRECTIONS as f32)).round() as usize % DIRECTIONS
+    }
+}
+
+// `assets/sprites/enemy.png` -> `assets/sprites/enemy.sheet`
+pub fn sheet_path(sprite_path: &str) -> PathBuf {
+    Path::new(sprite_path).with_extension(SHEET_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(columns: usize, loop_mode: LoopMode) -> SpriteSheet {
+        SpriteSheet {
+            columns,
+            frame_duration: 0.1,
+            loop_mode,
+            ..SpriteSheet::default()
+        }
+    }
+
+    // Frames shown in the middle of each of the first `count` frame slots
+    fn frames(sheet: &SpriteSheet, count: usize) -> Vec<usize> {
+        (0..count).map(|step| sheet.frame((step as f32 + 0.5) * sheet.frame_duration)).collect()
+    }
+
+    #[test]
+    fn parses_every_key() {
+        let sheet = SpriteSheet::parse(
+            "columns = 4   # frames\n\
+             rows = 8\n\
+             \n\
+             frame_time = 0.12\n\
+             loop = pingpong\n\
+             directional = true\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            sheet,
+            SpriteSheet {
+                columns: 4,
+                rows: 8,
+                frame_duration: 0.12,
+                loop_mode: LoopMode::PingPong,
+                directional: true,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_bad_sheets() {
+        assert!(SpriteSheet::parse("columns = 0\n").unwrap_err().starts_with("line 1:"));
+        assert!(SpriteSheet::parse("rows = 1\nloop = backwards\n").unwrap_err().starts_with("line 2:"));
+        assert!(SpriteSheet::parse("speed = 2\n").is_err());
+        assert!(SpriteSheet::parse("rows = 4\ndirectional = true\n").is_err());
+    }
+
+    #[test]
+    fn loop_starts_over_after_the_last_frame() {
+        assert_eq!(frames(&sheet(4, LoopMode::Loop), 9), vec![0, 1, 2, 3, 0, 1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        assert_eq!(frames(&sheet(4, LoopMode::Once), 7), vec![0, 1, 2, 3, 3, 3, 3]);
+        assert_eq!(sheet(4, LoopMode::Once).duration(), 0.4);
+    }
+
+    #[test]
+    fn pingpong_plays_back_without_repeating_the_ends() {
+        assert_eq!(frames(&sheet(4, LoopMode::PingPong), 9), vec![0, 1, 2, 3, 2, 1, 0, 1, 2]);
+        assert_eq!(frames(&sheet(1, LoopMode::PingPong), 3), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn a_phase_offset_shifts_the_frames() {
+        let sheet = sheet(4, LoopMode::Loop);
+        let offset: Vec<usize> = (0..4).map(|step| sheet.frame(0.25 + step as f32 * 0.1)).collect();
+
+        assert_eq!(offset, vec![2, 3, 0, 1]);
+        // Times before the start show the first frame
+        assert_eq!(sheet.frame(-1.0), 0);
+    }
+
+    #[test]
+    fn directions_are_picked_in_45_degree_sectors() {
+        let sheet = SpriteSheet { rows: DIRECTIONS, directional: true, ..SpriteSheet::default() };
+        let sector = PI / 4.0;
+
+        // A viewer looking east at entities facing every way in turn, starting
+        // with one facing back west at the viewer
+        for row in 0..DIRECTIONS {
+            let facing = PI + row as f32 * sector;
+            assert_eq!(sheet.direction(facing, 0.0), row);
+            assert_eq!(sheet.direction(facing + sector * 0.4, 0.0), row);
+            assert_eq!(sheet.direction(facing - sector * 0.4, 0.0), row);
+        }
+
+        // Only the angle between the two matters
+        assert_eq!(sheet.direction(1.0 + PI / 2.0, 1.0), 6);
+        assert_eq!(sheet.direction(1.0 - PI / 2.0, 1.0), 2);
+        assert_eq!(SpriteSheet::default().direction(0.0, 0.0), 0);
+    }
+}
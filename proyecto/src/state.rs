@@ -0,0 +1,183 @@
+use crate::input::{Action, PlayerCommand};
+
+// The screen the game is on. Each one handles its own input and drawing; the
+// front-end runs enter/exit hooks when `StateMachine` switches between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameState {
+    Title,
+    LevelSelect,
+    Playing,
+    Paused,
+    LevelComplete,
+    GameOver,
+    Settings,
+    // Best runs of the level selected in the menu
+    Leaderboard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    // Leaves every open screen for `GameState`
+    Switch(GameState),
+    // Opens a screen on top of the current one, which `Pop` goes back to
+    Push(GameState),
+    Pop,
+    Quit,
+}
+
+impl GameState {
+    // What the action keys do on this screen. Input that needs more than the
+    // action keys (the level list) and world events are handled by the
+    // front-end, which asks for its own transitions.
+    pub fn on_command(self, cmd: &PlayerCommand) -> Option<Transition> {
+        use GameState::*;
+        use Transition::*;
+
+        match self {
+            Title if cmd.has(Action::Confirm) => Some(Switch(LevelSelect)),
+            Title if cmd.has(Action::Settings) => Some(Push(Settings)),
+            Title if cmd.has(Action::Menu) => Some(Quit),
+            LevelSelect if cmd.has(Action::Settings) => Some(Push(Settings)),
+            LevelSelect if cmd.has(Action::Leaderboard) => Some(Push(Leaderboard)),
+            LevelSelect if cmd.has(Action::Menu) => Some(Switch(Title)),
+            Playing if cmd.has(Action::Menu) => Some(Push(Paused)),
+            Paused if cmd.has(Action::Menu) => Some(Pop),
+            Paused if cmd.has(Action::Settings) => Some(Push(Settings)),
+            LevelComplete if cmd.has(Action::Confirm) || cmd.has(Action::Menu) => Some(Switch(LevelSelect)),
+            GameOver if cmd.has(Action::Restart) => Some(Switch(Playing)),
+            GameOver if cmd.has(Action::Confirm) || cmd.has(Action::Menu) => Some(Switch(LevelSelect)),
+            Settings if cmd.has(Action::Menu) => Some(Pop),
+            Leaderboard if cmd.has(Action::Confirm) || cmd.has(Action::Menu) || cmd.has(Action::Leaderboard) => Some(Pop),
+            _ => None,
+        }
+    }
+}
+
+// The open screens, innermost last. Only the last one gets input; the ones
+// below it (the level under the pause screen) are kept to go back to.
+pub struct StateMachine {
+    stack: Vec<GameState>,
+    quit: bool,
+}
+
+impl StateMachine {
+    pub fn new(initial: GameState) -> Self {
+        Self {
+            stack: vec![initial],
+            quit: false,
+        }
+    }
+
+    pub fn current(&self) -> GameState {
+        *self.stack.last().expect("state stack is never empty")
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    // Applies `transition` and returns the (left, entered) states so the
+    // caller can run its exit and enter hooks. `None` when nothing changed:
+    // popping the last screen, switching to the screen already shown, quitting.
+    pub fn apply(&mut self, transition: Transition) -> Option<(GameState, GameState)> {
+        let from = self.current();
+
+        match transition {
+            Transition::Switch(to) => {
+                if self.stack.len() == 1 && from == to {
+                    return None;
+                }
+                self.stack.clear();
+                self.stack.push(to);
+            }
+            Transition::Push(to) => self.stack.push(to),
+            Transition::Pop => {
+                if self.stack.len() == 1 {
+                    return None;
+                }
+                self.stack.pop();
+            }
+            Transition::Quit => {
+                self.quit = true;
+                return None;
+            }
+        }
+
+        Some((from, self.current()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::GameState::*;
+
+    fn pressed(action: Action) -> PlayerCommand {
+        PlayerCommand {
+            actions: vec![action],
+            ..PlayerCommand::default()
+        }
+    }
+
+    // Feeds each key to the current screen and applies what it asks for
+    fn press_all(states: &mut StateMachine, actions: &[Action]) {
+        for action in actions {
+            if let Some(transition) = states.current().on_command(&pressed(*action)) {
+                states.apply(transition);
+            }
+        }
+    }
+
+    #[test]
+    fn restart_does_nothing_on_level_complete() {
+        assert_eq!(LevelComplete.on_command(&pressed(Action::Restart)), None);
+
+        let mut states = StateMachine::new(LevelComplete);
+        press_all(&mut states, &[Action::Restart]);
+        assert_eq!(states.current(), LevelComplete);
+        press_all(&mut states, &[Action::Confirm]);
+        assert_eq!(states.current(), LevelSelect);
+    }
+
+    #[test]
+    fn settings_opened_from_pause_go_back_to_pause() {
+        let mut states = StateMachine::new(Playing);
+        assert_eq!(states.apply(Transition::Push(Paused)), Some((Playing, Paused)));
+        assert_eq!(states.apply(Transition::Push(Settings)), Some((Paused, Settings)));
+        assert_eq!(states.apply(Transition::Pop), Some((Settings, Paused)));
+        assert_eq!(states.apply(Transition::Pop), Some((Paused, Playing)));
+
+        // The same through the keys
+        press_all(&mut states, &[Action::Menu, Action::Settings, Action::Menu]);
+        assert_eq!(states.current(), Paused);
+        press_all(&mut states, &[Action::Menu]);
+        assert_eq!(states.current(), Playing);
+    }
+
+    #[test]
+    fn pop_on_the_last_screen_changes_nothing() {
+        let mut states = StateMachine::new(Title);
+        assert_eq!(states.apply(Transition::Pop), None);
+        assert_eq!(states.current(), Title);
+        assert!(!states.should_quit());
+    }
+
+    #[test]
+    fn switch_leaves_every_open_screen() {
+        let mut states = StateMachine::new(Playing);
+        states.apply(Transition::Push(Paused));
+        states.apply(Transition::Push(Settings));
+        assert_eq!(states.apply(Transition::Switch(Title)), Some((Settings, Title)));
+        assert_eq!(states.apply(Transition::Pop), None);
+        // Already there
+        assert_eq!(states.apply(Transition::Switch(Title)), None);
+    }
+
+    #[test]
+    fn menu_on_the_title_quits() {
+        let mut states = StateMachine::new(Title);
+        press_all(&mut states, &[Action::Menu]);
+        assert!(states.should_quit());
+        assert_eq!(states.current(), Title);
+    }
+}
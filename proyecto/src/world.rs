@@ -0,0 +1,185 @@
+use crate::input::{Action, PlayerCommand};
+use crate::door::DoorKind;
+use crate::map::Map;
+use crate::player::Player;
+use crate::sprite::SpriteManager;
+use crate::entity::{self, Behaviour};
+
+// Things that happened during one simulation step, for the front-end to react to
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorldEvent {
+    CoinCollected(f32, f32),
+    // Health after the pack
+    HealthCollected(i32),
+    KeyCollected,
+    LevelCompleted,
+    // Health left after the hit
+    PlayerHurt(i32),
+    PlayerDied,
+    // A door at this cell centre started to open
+    DoorOpened(f32, f32),
+    // The player tried a locked door without a key
+    DoorLocked,
+}
+
+// How far in front of the player the use key reaches, in cells
+const USE_DISTANCE: f32 = 1.2;
+// Automatic doors open when the player is this close to their centre
+const AUTO_OPEN_DISTANCE: f32 = 1.6;
+
+// Simulation state of the level being played, with no rendering or input
+pub struct World {
+    pub player: Player,
+    pub map: Map,
+    pub sprites: SpriteManager,
+    pub current_level: usize,
+    pub level_complete: bool,
+    // Keys picked up on this attempt
+    pub keys: u32,
+    // Seconds played on this attempt, stopped when the level is completed
+    pub elapsed: f32,
+}
+
+impl World {
+    // `sparkle_lifetime` comes from the sparkle's sprite sheet, which the
+    // caller loads once
+    pub fn new(level: usize, sparkle_lifetime: f32) -> Self {
+        Self::from_map(level, Map::new(level), sparkle_lifetime)
+    }
+
+    pub fn from_map(level: usize, map: Map, sparkle_lifetime: f32) -> Self {
+        let sprites = SpriteManager::new(&map, sparkle_lifetime);
+        let (spawn_x, spawn_y, spawn_angle) = map.spawn;
+
+        Self {
+            player: Player::new(spawn_x, spawn_y, spawn_angle),
+            map,
+            sprites,
+            current_level: level,
+            level_complete: false,
+            keys: 0,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn restart(&mut self) {
+        *self = Self::new(self.current_level, self.sprites.sparkle_lifetime);
+    }
+
+    // Advances the simulation by `dt` seconds
+    pub fn update(&mut self, cmd: &PlayerCommand, dt: f32) -> Vec<WorldEvent> {
+        let mut events = Vec::new();
+
+        // Nothing moves once the player is dead
+        if !self.player.is_alive() {
+            return events;
+        }
+
+        if !self.level_complete {
+            self.elapsed += dt;
+        }
+        if cmd.has(Action::Use) {
+            self.use_door(&mut events);
+        }
+        self.player.update(cmd, dt, &self.map);
+
+        for (behaviour, x, y) in self.sprites.collect_pickups(&self.player) {
+            match behaviour {
+                Behaviour::Coin => {
+                    self.sprites.spawn_sparkle(x, y);
+                    events.push(WorldEvent::CoinCollected(x, y));
+                }
+                Behaviour::HealthPack => {
+                    self.player.heal(entity::HEALTH_PACK_AMOUNT);
+                    events.push(WorldEvent::HealthCollected(self.player.health));
+                }
+                Behaviour::Key => {
+                    self.keys += 1;
+                    events.push(WorldEvent::KeyCollected);
+                }
+                Behaviour::Decoration | Behaviour::Enemy | Behaviour::Effect => {}
+            }
+        }
+
+        if !self.level_complete && self.sprites.all_coins_collected() {
+            self.level_complete = true;
+            events.push(WorldEvent::LevelCompleted);
+        }
+
+        let damage = self.sprites.update_enemies(dt, &self.player, &self.map);
+        if !self.level_complete && damage > 0 && self.player.take_damage(damage) {
+            events.push(WorldEvent::PlayerHurt(self.player.health));
+            if !self.player.is_alive() {
+                events.push(WorldEvent::PlayerDied);
+            }
+        }
+
+        self.update_doors(dt, &mut events);
+        self.sprites.update_animation(dt);
+
+        events
+    }
+
+    // Opens the first door within reach straight ahead. Locked doors take a
+    // key the first time and stay unlocked after that.
+    fn use_door(&mut self, events: &mut Vec<WorldEvent>) {
+        let (dir_x, dir_y) = self.player.get_direction();
+        let steps = 4;
+        let cell = (1..=steps)
+            .map(|i| {
+                let reach = USE_DISTANCE * i as f32 / steps as f32;
+                (self.player.x + dir_x * reach, self.player.y + dir_y * reach)
+            })
+            .filter(|(x, y)| *x >= 0.0 && *y >= 0.0)
+            .map(|(x, y)| (x as usize, y as usize))
+            .find(|(x, y)| self.map.door_at(*x, *y).is_some());
+        let Some(door) = cell.and_then(|(x, y)| self.map.door_at_mut(x, y)) else {
+            return;
+        };
+
+        if door.kind == DoorKind::Locked {
+            if self.keys == 0 {
+                events.push(WorldEvent::DoorLocked);
+                return;
+            }
+            self.keys -= 1;
+            door.kind = DoorKind::Use;
+        }
+        if door.open() {
+            let (x, y) = door.center();
+            events.push(WorldEvent::DoorOpened(x, y));
+        }
+    }
+
+    fn update_doors(&mut self, dt: f32, events: &mut Vec<WorldEvent>) {
+        let player = &self.player;
+        let enemies: Vec<(f32, f32)> = self
+            .sprites
+            .entities
+            .iter()
+            .filter(|entity| entity.behaviour == Behaviour::Enemy)
+            .map(|entity| (entity.x, entity.y))
+            .collect();
+
+        for door in &mut self.map.doors {
+            let (center_x, center_y) = door.center();
+            if door.kind == DoorKind::Auto
+                && ((player.x - center_x).powi(2) + (player.y - center_y).powi(2)).sqrt() < AUTO_OPEN_DISTANCE
+                && door.open()
+            {
+                events.push(WorldEvent::DoorOpened(center_x, center_y));
+            }
+
+            // The player's circle overlapping the cell, or an enemy inside it
+            let (left, top) = (door.x as f32, door.y as f32);
+            let closest_x = player.x.clamp(left, left + 1.0);
+            let closest_y = player.y.clamp(top, top + 1.0);
+            let player_inside = (player.x - closest_x).powi(2) + (player.y - closest_y).powi(2) < player.radius.powi(2);
+            let enemy_inside = enemies
+                .iter()
+                .any(|(x, y)| x.floor() as usize == door.x && y.floor() as usize == door.y);
+
+            door.update(dt, player_inside || enemy_inside);
+        }
+    }
+}
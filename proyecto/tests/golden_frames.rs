@@ -0,0 +1,102 @@
+// Renders each built-in level with `--headless --no-textures` and compares
+// the frames with the reference images in tests/golden. After an intended
+// change to the renderer or the levels, run with UPDATE_GOLDEN=1 to write new
+// references, and look at them before committing.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const GOLDEN_DIR: &str = "tests/golden";
+const SIZE: &str = "160x120";
+// Pixels may differ by this much per channel, for float rounding between
+// platforms, and only this fraction of the frame may differ at all
+const CHANNEL_TOLERANCE: u8 = 8;
+const MAX_DIFFERENT_PIXELS: f32 = 0.01;
+
+// Level number and pose, each facing something worth checking: level 1 looks
+// across the room at the middle pillar, level 2 down the corridor at the
+// central pillar and a coin, level 3 north between two blocks at a closed door
+const VIEWS: [(usize, &str); 3] = [
+    (1, "3.5,8.5,-0.6"),
+    (2, "7.5,2.5,1.571"),
+    (3, "4.5,7.5,-1.571"),
+];
+
+struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+fn read_png(path: &Path) -> Image {
+    let file = File::open(path).unwrap_or_else(|e| panic!("{}: {} (UPDATE_GOLDEN=1 writes it)", path.display(), e));
+    let mut reader = png::Decoder::new(file).read_info().expect("reference is a PNG");
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).expect("reference decodes");
+    assert_eq!(info.color_type, png::ColorType::Rgba, "{} is not RGBA", path.display());
+    pixels.truncate(info.buffer_size());
+
+    Image {
+        width: info.width,
+        height: info.height,
+        pixels,
+    }
+}
+
+// Renders `level` from `pose` with the game binary, into `out`
+fn render(level: usize, pose: &str, out: &Path) {
+    let output = Command::new(env!("CARGO_BIN_EXE_proyecto"))
+        .args(["--headless", "--level", &level.to_string(), "--pose", pose, "--size", SIZE, "--no-textures"])
+        .arg("--out")
+        .arg(out)
+        .output()
+        .expect("game binary runs");
+
+    assert!(
+        output.status.success(),
+        "level {} failed to render: {}",
+        level,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn levels_render_like_the_reference_frames() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+
+    for (level, pose) in VIEWS {
+        let golden = PathBuf::from(GOLDEN_DIR).join(format!("level{}.png", level));
+        if update {
+            render(level, pose, &golden);
+            continue;
+        }
+
+        let actual_path = std::env::temp_dir().join(format!("proyecto-golden-level{}.png", level));
+        render(level, pose, &actual_path);
+        let expected = read_png(&golden);
+        let actual = read_png(&actual_path);
+        assert_eq!((actual.width, actual.height), (expected.width, expected.height), "level {} size", level);
+
+        let different = actual
+            .pixels
+            .chunks(4)
+            .zip(expected.pixels.chunks(4))
+            .filter(|(a, b)| a.iter().zip(b.iter()).any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE))
+            .count();
+        let total = (actual.width * actual.height) as usize;
+        if different as f32 > total as f32 * MAX_DIFFERENT_PIXELS {
+            failures.push(format!(
+                "level {}: {} of {} pixels differ from {}, frame written to {}",
+                level,
+                different,
+                total,
+                golden.display(),
+                actual_path.display()
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
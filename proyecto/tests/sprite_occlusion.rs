@@ -0,0 +1,146 @@
+// Renders small levels without a window and compares frames with and without
+// a coin to find the pixels the coin's sprite covers.
+
+use std::path::Path;
+use proyecto::entity::COIN_SPRITE;
+use proyecto::framebuffer::{rgba, FrameBuffer, TextureData};
+use proyecto::headless;
+use proyecto::level;
+use proyecto::map::Map;
+use proyecto::raycast::RayCaster;
+use proyecto::sprite_sheet::SpriteSheet;
+
+const WIDTH: usize = 200;
+const HEIGHT: usize = 100;
+
+// The player looks east along y = 3 at a coin 4 cells away. The wall cell at
+// (3, 2) stands between them and covers the coin's left half (y < 3); its
+// right half is in the open.
+const PILLAR_LEVEL: &str = "\
+spawn = 1.5 3.0 0.0
+coin = 5.5 3.0
+
+[grid]
+11111111
+10000001
+10010001
+10000001
+10000001
+11111111
+";
+
+const OPEN_LEVEL: &str = "\
+spawn = 1.5 3.0 0.0
+coin = 5.5 3.0
+
+[grid]
+11111111
+10000001
+10000001
+10000001
+10000001
+11111111
+";
+
+fn load_map(source: &str, with_coin: bool) -> Map {
+    let source = if with_coin {
+        source.to_string()
+    } else {
+        source.lines().filter(|line| !line.starts_with("coin")).collect::<Vec<_>>().join("\n")
+    };
+    let level = level::parse_level(Path::new("test.level"), &source).expect("test level parses");
+    Map::from_level(level)
+}
+
+fn solid_texture(color: u32) -> TextureData {
+    TextureData::new(4, 4, vec![color; 16])
+}
+
+fn render(source: &str, with_coin: bool, texture: TextureData) -> FrameBuffer {
+    let mut raycaster = RayCaster::new(WIDTH, HEIGHT);
+    raycaster.add_sprite_texture(COIN_SPRITE.to_string(), texture, SpriteSheet::default());
+    let map = load_map(source, with_coin);
+    headless::render_frame(&mut raycaster, &map, None);
+    raycaster.into_framebuffer()
+}
+
+// Pixels that changed when the coin was added, per column
+fn sprite_pixels(source: &str, texture: fn() -> TextureData) -> Vec<Vec<usize>> {
+    let without = render(source, false, texture());
+    let with = render(source, true, texture());
+
+    (0..WIDTH)
+        .map(|x| (0..HEIGHT).filter(|y| with.get_pixel(x, *y) != without.get_pixel(x, *y)).collect())
+        .collect()
+}
+
+fn opaque() -> TextureData {
+    solid_texture(rgba(255, 0, 255, 255))
+}
+
+#[test]
+fn sprite_half_behind_a_wall_is_cut_at_the_wall_edge() {
+    let columns = sprite_pixels(PILLAR_LEVEL, opaque);
+    let center = WIDTH / 2;
+
+    // The coin is 4 cells away and half a cell wide, about 21 pixels on screen
+    let left: usize = columns[center - 8..center - 1].iter().map(|column| column.len()).sum();
+
+    assert_eq!(left, 0, "coin drawn through the wall");
+    assert!(columns[center + 1..center + 8].iter().all(|column| !column.is_empty()), "visible half missing");
+}
+
+#[test]
+fn sprite_in_the_open_is_drawn_whole() {
+    let columns = sprite_pixels(OPEN_LEVEL, opaque);
+    let center = WIDTH / 2;
+
+    assert!(columns[center - 8..center + 8].iter().all(|column| !column.is_empty()));
+}
+
+#[test]
+fn transparent_pixels_keep_the_background() {
+    // Top half clear, bottom half opaque
+    fn half_clear() -> TextureData {
+        let clear = rgba(255, 0, 255, 0);
+        let solid = rgba(255, 0, 255, 255);
+        TextureData::new(4, 4, (0..16).map(|i| if i < 8 { clear } else { solid }).collect())
+    }
+
+    let columns = sprite_pixels(OPEN_LEVEL, half_clear);
+    let center = WIDTH / 2;
+    let opaque_columns = sprite_pixels(OPEN_LEVEL, opaque);
+
+    for x in center - 8..center + 8 {
+        let full = &opaque_columns[x];
+        let drawn = &columns[x];
+        let middle = (full.first().unwrap() + full.last().unwrap()) / 2;
+
+        assert!(!drawn.is_empty());
+        assert!(drawn.iter().all(|y| *y >= middle), "clear pixels drawn in column {}", x);
+    }
+}
+
+#[test]
+fn translucent_pixels_are_blended() {
+    fn translucent() -> TextureData {
+        solid_texture(rgba(255, 0, 255, 128))
+    }
+
+    let without = render(OPEN_LEVEL, false, opaque());
+    let solid = render(OPEN_LEVEL, true, opaque());
+    let blended = render(OPEN_LEVEL, true, translucent());
+    let (x, y) = (WIDTH / 2, HEIGHT / 2);
+
+    let background = without.get_pixel(x, y);
+    let sprite = solid.get_pixel(x, y);
+    let mixed = blended.get_pixel(x, y);
+    assert_ne!(mixed, background);
+    assert_ne!(mixed, sprite);
+
+    // Every channel lies between the background and the sprite
+    for channel in 0..3 {
+        let [b, s, m] = [background, sprite, mixed].map(|color| color.to_ne_bytes()[channel]);
+        assert!(b.min(s) <= m && m <= b.max(s));
+    }
+}